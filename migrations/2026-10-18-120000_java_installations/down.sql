@@ -0,0 +1 @@
+DROP TABLE java_installations
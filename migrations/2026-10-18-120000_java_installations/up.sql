@@ -0,0 +1,9 @@
+CREATE TABLE java_installations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE,
+    vendor TEXT NOT NULL,
+    version TEXT NOT NULL,
+    major_version INTEGER NOT NULL,
+    arch TEXT NOT NULL,
+    source TEXT NOT NULL
+)
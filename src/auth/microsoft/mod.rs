@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 pub use serve::await_token;
 
-use crate::{client, error::Result};
+use crate::{http::client, error::Result};
 
 #[derive(Debug, Deserialize)]
 struct MicrosoftResponse {
@@ -123,7 +123,7 @@ pub fn get_microsoft_auth_uri() -> String {
 }
 
 pub async fn microsoft_login(code: &str) -> Result<String> {
-    let access_token = client
+    let access_token = client()
         .post("https://login.live.com/oauth20_token.srf")
         .header("Content-Type", "application/x-www-form-urlencoded")
         .body(format!(
@@ -149,7 +149,7 @@ pub async fn microsoft_login(code: &str) -> Result<String> {
     let XboxResponse {
         token,
         display_claims,
-    } = client
+    } = client()
         .post("https://user.auth.xboxlive.com/user/authenticate")
         .header("Accept", "application/json")
         .json(&xbox_payload)
@@ -168,7 +168,7 @@ pub async fn microsoft_login(code: &str) -> Result<String> {
         ..Default::default()
     };
 
-    let XboxResponse { token, .. } = client
+    let XboxResponse { token, .. } = client()
         .post("https://xsts.auth.xboxlive.com/xsts/authorize")
         .json(&xsts_payload)
         .send()
@@ -181,7 +181,7 @@ pub async fn microsoft_login(code: &str) -> Result<String> {
         ..Default::default()
     };
 
-    let access_token = client
+    let access_token = client()
         .post("https://api.minecraftservices.com/authentication/login_with_xbox")
         .json(&mojang_payload)
         .send()
@@ -3,7 +3,7 @@ use reqwest::{Response, StatusCode};
 use serde::{Serialize, Deserialize};
 use tracing::{error, info};
 use crate::error::{Error, Result, MojangAuthError};
-use crate::client;
+use crate::http::client;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -50,7 +50,7 @@ pub async fn mojang_login(username: &str, password: &str) -> Result<String> {
 
     info!("trying Mojang authentication with username {}", payload.username);
 
-    let response = client
+    let response = client()
         .post("https://authserver.mojang.com/authenticate")
         .json(&payload)
         .send()
@@ -1,7 +1,7 @@
 use reqwest::StatusCode;
 use serde::{Serialize, Deserialize};
 use crate::error::{Error, ProfileError};
-use crate::client;
+use crate::http::client;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,7 +11,7 @@ pub struct Profile {
 }
 
 pub async fn get_profile(access_token: &str) -> Result<Profile, Error> {
-    let response = client.get("https://api.minecraftservices.com/minecraft/profile")
+    let response = client().get("https://api.minecraftservices.com/minecraft/profile")
         .bearer_auth(access_token)
         .send()
         .await?;
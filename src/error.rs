@@ -27,9 +27,17 @@ pub enum ProfileError {
     BadAccessToken
 }
 
+#[derive(Debug, Error)]
+pub enum AssetIndexError {
+    #[error("asset index is not valid")]
+    Parse(#[from] serde_json::Error),
+    #[error("asset index has {actual} bytes, expected {expected}")]
+    SizeMismatch { expected: usize, actual: usize },
+}
+
 #[derive(Debug)]
 pub enum StartupRequirement {
-    Account, Assets, Client, Libraries, Java
+    Account, Assets, Client, Libraries, Java, Server, Eula
 }
 
 #[derive(Debug, Error)]
@@ -46,6 +54,12 @@ pub enum Error {
     ProfileError(#[from] ProfileError),
     #[error("unknown network error")]
     NetworkError(#[from] reqwest::Error),
+    #[error("version {0} not found")]
+    VersionNotFound(String),
+    #[error("version {0} has no obfuscation mappings")]
+    MappingsNotAvailable(String),
+    #[error("bad mappings at line {0}: {1}")]
+    MappingsParseError(usize, String),
     #[error("could not find needed java version")]
     JavaVersionNotFoundError,
     #[error("could not connect to embedded database")]
@@ -54,8 +68,45 @@ pub enum Error {
     DatabaseError(#[from] diesel::result::Error),
     #[error("error in file io")]
     FileIOError(#[from] std::io::Error),
-    #[error("file already exists {0}")]
-    FileExists(String),
+    #[error("{} required downloads failed", .0.failed.len())]
+    IncompleteDownload(crate::resources::download::DownloadReport),
+    #[error("{0} is not available in the offline cache")]
+    NotCached(String),
+    #[error("sha1 mismatch for {path}: expected {expected}, got {actual}")]
+    Sha1Mismatch { path: String, expected: String, actual: String },
+    #[error("sha256 mismatch for {path}: expected {expected}, got {actual}")]
+    Sha256Mismatch { path: String, expected: String, actual: String },
+    #[error("error loading asset index")]
+    AssetIndexError(#[from] AssetIndexError),
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+    #[error("{url} returned HTTP {status}")]
+    HttpStatus { url: String, status: u16, retry_after: Option<std::time::Duration> },
+    #[error("download of {url} failed after {} attempts: {}", .attempts.len(), display_attempts(.attempts))]
+    DownloadFailed { url: String, attempts: Vec<Error> },
+    #[error("size mismatch for {path}: expected {expected} bytes, got {actual}")]
+    SizeMismatch { path: String, expected: usize, actual: usize },
+    #[error("{path} is not a working java installation: {reason}")]
+    InvalidJava { path: String, reason: String },
+    #[error("invalid path pattern")]
+    PatternError(#[from] glob::PatternError),
+    #[error("could not extract {path}: {reason}")]
+    ArchiveError { path: String, reason: String },
+    #[error("no stored version needs a java runtime, refusing to remove every managed runtime")]
+    NoStoredVersions,
+    #[error("the download job was cancelled")]
+    Cancelled,
+    #[error("could not parse json")]
+    JsonError(#[from] serde_json::Error),
     #[error("error ocurred at game startup or during execution")]
     GameProfileError(#[from] GameProfileError)
-}
\ No newline at end of file
+}
+
+fn display_attempts(attempts: &[Error]) -> String {
+    attempts
+        .iter()
+        .enumerate()
+        .map(|(i, e)| format!("[{}] {}", i + 1, e))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,8 +1,7 @@
-use std::{iter::{Map, Filter}, path::PathBuf};
+use std::{iter::{Map, Filter}, path::{Path, PathBuf}};
 
 use convert_case::Casing;
 use tracing::info;
-use glob::glob;
 
 use crate::{
     error::{Error, GameProfileError, Result, StartupRequirement},
@@ -26,46 +25,37 @@ impl Profile {
         }
     }
 
-    //TODO: dynamic java path resolution
     pub async fn run(&self, account: &Account) -> Result<()> {
         self.check_requirements().await?;
+        // removed when dropped, once the game has exited
+        let natives = self.details.natives_directory()?;
         
-        let _ = tokio::process::Command::new(self.java_path())
-            .args(self.fill_dynamic_args(account))
+        let _ = tokio::process::Command::new(self.java_path().await?)
+            .args(self.fill_dynamic_args(account, natives.path()))
             .spawn()?
             .wait()
             .await;
-        
-        tokio::fs::remove_dir_all(path_with_launcher("natives")).await?;
 
         Ok(())
     }
 
-    fn java_path(&self) -> PathBuf {
-        //don't like copying
-        glob(&(path_with_launcher("jdk/") + "*" + &self.details.java_version.major_version.to_string() +  "*/bin/java"))
-            .unwrap()
-            .next()
-            .unwrap()
-            .unwrap()
+    async fn java_path(&self) -> Result<PathBuf> {
+        self.details.java_path().await.ok_or(Error::JavaVersionNotFoundError)
     }
 
     fn parse_arguments(details: &VersionDetails) -> Vec<String> {
-        let mut jvm_args = Self::filter_args(&details.arguments.jvm)
-            .iter()
-            .map(|arg| {
-                let arg = arg.simple().unwrap();
-                arg.to_owned()
-            })
-            .collect::<Vec<_>>();
-
-        let mut game_args = Self::filter_args(&details.arguments.game)
-            .iter()
-            .map(|arg| {
-                let arg = arg.simple().unwrap();
-                arg.to_owned()
-            })
-            .collect::<Vec<_>>();
+        let (mut jvm_args, mut game_args) = match (&details.arguments, &details.minecraft_arguments) {
+            (Some(arguments), _) => (Self::simple_args(&arguments.jvm), Self::simple_args(&arguments.game)),
+            // before 1.13 the game arguments are a single string and the JVM ones are implied
+            (None, minecraft_arguments) => (
+                ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"].map(String::from).to_vec(),
+                minecraft_arguments
+                    .iter()
+                    .flat_map(|arguments| arguments.split_whitespace())
+                    .map(String::from)
+                    .collect(),
+            ),
+        };
 
         jvm_args.push(String::from("net.minecraft.client.main.Main"));
         
@@ -74,6 +64,16 @@ impl Profile {
         jvm_args
     }
 
+    fn simple_args(arguments: &[Argument]) -> Vec<String> {
+        Self::filter_args(arguments)
+            .iter()
+            .map(|arg| {
+                let arg = arg.simple().unwrap();
+                arg.to_owned()
+            })
+            .collect()
+    }
+
     fn filter_args(arguments: &[Argument]) -> Vec<&Argument>{
         arguments.iter().filter(|arg| 
            match arg {
@@ -89,12 +89,13 @@ impl Profile {
         arguments
             .iter()
             .map(|arg| match arg.as_ref() {
-                "-Djava.library.path=${natives_directory}" => format!("-Djava.library.path=${}", path_with_launcher("natives")),
                 "${classpath}" => (details
                     .libraries
                     .iter()
-                    .fold(String::new(), |acc, library| {
-                        acc + &path_with_launcher("libraries/") + &library.downloads.artifact.path + ":"
+                    .filter(|library| library.is_allowed())
+                    .filter_map(|library| library.downloads.artifact.as_ref())
+                    .fold(String::new(), |acc, artifact| {
+                        acc + &artifact.local_path() + ":"
                     }) + &path_with_launcher("client/") + &details.assets + "/client.jar"),
                     "${assets_root}" => path_with_launcher("assets"),
                 "${assets_index_name}" => details.assets.clone(),
@@ -107,10 +108,11 @@ impl Profile {
             .collect()
     }
     
-    fn fill_dynamic_args(&self, account: &Account) -> Vec<String> {
+    fn fill_dynamic_args(&self, account: &Account, natives: &Path) -> Vec<String> {
         self.arguments
             .iter()
             .map(|arg| match arg.as_ref() {
+                "-Djava.library.path=${natives_directory}" => format!("-Djava.library.path={}", natives.display()),
                 "${auth_player_name}" => account.username.clone(),
                 "${auth_uuid}" => account.account_uuid.clone(),
                 "${auth_access_token}" => account.access_token.clone(),
@@ -134,7 +136,7 @@ impl Profile {
             Err(Error::GameProfileError(
                 GameProfileError::RequirementFailed(StartupRequirement::Client),
             ))
-        } else if !self.details.check_jdk() {
+        } else if !self.details.check_jdk().await {
             Err(Error::GameProfileError(
                 GameProfileError::RequirementFailed(StartupRequirement::Java),
             ))
@@ -148,6 +150,8 @@ impl Profile {
 #[cfg(test)]
 mod tests {
     use crate::game_profile::Profile;
+    use crate::resources::version::tests::legacy_details;
+    use crate::resources::progress::Progress;
     use tracing::info;
 
     #[tokio::test]
@@ -156,7 +160,7 @@ mod tests {
         let versions = crate::resources::version::get_available_versions()
             .await
             .unwrap();
-        let latest = versions.latest_release().get_details().await.unwrap();
+        let latest = versions.latest_release().unwrap().get_details().await.unwrap();
 
         let profile = Profile {
             name: String::from(""),
@@ -168,16 +172,24 @@ mod tests {
         profile.check_requirements().await.unwrap();
     }
 
+    #[test]
+    fn parses_legacy_arguments() {
+        let arguments = Profile::parse_arguments(&legacy_details());
+        assert_eq!(arguments[..4], ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}", "net.minecraft.client.main.Main"]);
+        assert_eq!(arguments[4..6], ["--username", "${auth_player_name}"]);
+        assert_eq!(arguments.last().unwrap(), "${version_type}");
+    }
+
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn run() {
         let versions = crate::resources::version::get_available_versions()
             .await
             .unwrap();
-        let latest = versions.latest_release().get_details().await.unwrap();
+        let latest = versions.latest_release().unwrap().get_details().await.unwrap();
         //latest.download_client().await.unwrap();
-        latest.download_libraries(|_, _| {}).await.unwrap();
-        latest.download_assets(|_, _| {}).await.unwrap();
+        latest.download_libraries(&Progress::none()).await.unwrap();
+        latest.download_assets(&Progress::none()).await.unwrap();
         latest.extract_natives().unwrap();
         
         let profile = Profile::new(&latest, "1.19");
@@ -0,0 +1,158 @@
+use std::{path::PathBuf, sync::RwLock, time::Duration};
+
+use lazy_static::lazy_static;
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+
+use crate::error::Result;
+
+/// Settings for the HTTP client shared by every request the launcher makes.
+///
+/// How long a download may wait for headers or stall mid-body is set per download
+/// through `DownloadConfig::connect_timeout` and `DownloadConfig::read_timeout`; `timeout`
+/// only bounds the other requests, whose bodies are small.
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    /// Proxy for every request, e.g. `http://proxy.corp:3128` or `socks5://127.0.0.1:1080`.
+    /// When unset, the `HTTP_PROXY`/`HTTPS_PROXY` environment variables are honoured.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates, for proxies that intercept TLS.
+    pub ca_bundle: Option<PathBuf>,
+    pub user_agent: String,
+    /// How long to wait for the TCP and TLS handshakes.
+    pub connect_timeout: Option<Duration>,
+    /// How long a whole request may take, from connecting to reading the end of the body,
+    /// for metadata and authentication requests.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            ca_bundle: None,
+            user_agent: format!("liblauncher/{}", env!("CARGO_PKG_VERSION")),
+            connect_timeout: Some(Duration::from_secs(30)),
+            timeout: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl HttpSettings {
+    pub fn build_client(&self) -> Result<Client> {
+        let mut builder = self.builder()?;
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// A client for file downloads, which can take far longer than `timeout` and are bounded
+    /// by `DownloadConfig` instead.
+    fn build_download_client(&self) -> Result<Client> {
+        Ok(self.builder()?.build()?)
+    }
+
+    fn builder(&self) -> Result<ClientBuilder> {
+        let mut builder = Client::builder().user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(ca_bundle) = &self.ca_bundle {
+            for certificate in Certificate::from_pem_bundle(&std::fs::read(ca_bundle)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        Ok(builder)
+    }
+}
+
+lazy_static! {
+    static ref HTTP: RwLock<(HttpSettings, Client, Client)> = {
+        let settings = HttpSettings::default();
+        let client = settings.build_client().unwrap();
+        let download_client = settings.build_download_client().unwrap();
+        RwLock::new((settings, client, download_client))
+    };
+}
+
+/// Rebuilds the shared client from `settings`. Requests already in flight keep the old client.
+pub fn set_http_settings(settings: HttpSettings) -> Result<()> {
+    let client = settings.build_client()?;
+    let download_client = settings.build_download_client()?;
+    *HTTP.write().unwrap() = (settings, client, download_client);
+    Ok(())
+}
+
+pub fn http_settings() -> HttpSettings {
+    HTTP.read().unwrap().0.clone()
+}
+
+pub(crate) fn client() -> Client {
+    HTTP.read().unwrap().1.clone()
+}
+
+/// The shared client without the overall `timeout`, for file downloads.
+pub(crate) fn download_client() -> Client {
+    HTTP.read().unwrap().2.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_test::traced_test;
+    use warp::Filter;
+
+    use super::HttpSettings;
+    use crate::error::Error;
+
+    #[tokio::test]
+    #[traced_test]
+    async fn sends_requests_through_proxy() {
+        let filter = warp::path::full()
+            .and(warp::header::<String>("user-agent"))
+            .map(|path: warp::path::FullPath, agent: String| format!("{} {}", path.as_str(), agent));
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpSettings {
+            proxy: Some(format!("http://{addr}")),
+            user_agent: String::from("test-agent"),
+            ..Default::default()
+        }
+        .build_client()
+        .unwrap();
+        let body = client.get("http://resources.invalid/ab/cd").send().await.unwrap().text().await.unwrap();
+        assert_eq!(body, "/ab/cd test-agent");
+    }
+
+    #[test]
+    fn rejects_missing_ca_bundle() {
+        let settings = HttpSettings {
+            ca_bundle: Some(std::env::temp_dir().join("liblauncher-missing-ca.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(settings.build_client(), Err(Error::FileIOError(_))));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn times_out_stalled_requests() {
+        let filter = warp::any().then(|| async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            "late"
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = HttpSettings {
+            timeout: Some(std::time::Duration::from_millis(100)),
+            ..Default::default()
+        }
+        .build_client()
+        .unwrap();
+        let error = client.get(format!("http://{addr}/")).send().await.unwrap_err();
+        assert!(error.is_timeout());
+    }
+}
@@ -4,11 +4,15 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use lazy_static::lazy_static;
 pub mod auth;
 pub mod game_profile;
+pub mod server;
 pub mod resources;
 pub mod error;
+pub mod http;
 
 mod store;
 mod schema;
@@ -16,29 +20,44 @@ mod schema;
 embed_migrations!();
 
 lazy_static! {
-    static ref client: reqwest::Client = reqwest::Client::new();
     static ref config: Config = Config::new();
 }
 
 struct Config {
-    launcher_path: String
+    launcher_path: String,
+    offline: AtomicBool,
 }
 
 impl Config {
     fn new() -> Self {
         let launcher_path = std::env::var("LAUNCHER_PATH");
+        let offline = std::env::var("LAUNCHER_OFFLINE").map(|v| v == "1" || v == "true");
         Self {
-            launcher_path: launcher_path.unwrap_or_else(|_| Config::default().launcher_path )
+            launcher_path: launcher_path.unwrap_or_else(|_| Config::default().launcher_path ),
+            offline: AtomicBool::new(offline.unwrap_or(false)),
         }
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { launcher_path:  format!("{}/liblauncher", dirs::data_dir().unwrap().to_str().unwrap()) }
+        Self {
+            launcher_path:  format!("{}/liblauncher", dirs::data_dir().unwrap().to_str().unwrap()),
+            offline: AtomicBool::new(false),
+        }
     }
 }
 
+/// Serve version metadata from the local cache only, never touching the network.
+/// Defaults to the `LAUNCHER_OFFLINE` environment variable.
+pub fn set_offline_mode(offline: bool) {
+    config.offline.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline_mode() -> bool {
+    config.offline.load(Ordering::Relaxed)
+}
+
 fn path_with_launcher(path: &str) -> String {
     format!("{}/{path}", config.launcher_path)
 }
@@ -47,7 +66,7 @@ fn path_with_launcher(path: &str) -> String {
 mod tests {
     use tracing::info;
 
-    use crate::{game_profile::Profile, store};
+    use crate::{game_profile::Profile, resources::progress::Progress, store};
 
 
     #[tokio::test]
@@ -64,12 +83,12 @@ mod tests {
         let versions = crate::resources::version::get_available_versions()
             .await
             .unwrap();
-        let latest = versions.release().iter().find(|version| version.id == "1.18").unwrap().get_details().await.unwrap();
+        let latest = versions.get("1.18").unwrap().get_details().await.unwrap();
         info!("holados? {}", super::config.launcher_path);
         latest.download_client().await.unwrap();
-        //latest.download_jdk().await.unwrap();
-        latest.download_libraries(|_, _| {}).await.unwrap();
-        latest.download_assets(|_, _| {}).await.unwrap();
+        //latest.download_jdk(&Progress::none()).await.unwrap();
+        latest.download_libraries(&Progress::none()).await.unwrap();
+        latest.download_assets(&Progress::none()).await.unwrap();
         
         latest.extract_natives().unwrap();
         
@@ -0,0 +1,540 @@
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use xz2::read::XzDecoder;
+
+use crate::error::{Error, Result};
+
+/// Archive formats runtimes and JDKs are shipped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Recognizes an archive by its leading magic bytes.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(ArchiveFormat::TarXz)
+        } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Bounds on what an archive may unpack, so a hostile or corrupt archive can't fill the disk.
+#[derive(Debug, Clone)]
+pub struct ExtractLimits {
+    pub max_entries: usize,
+    /// Total bytes of every extracted file.
+    pub max_total_size: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 100_000,
+            max_total_size: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Extracts the archive at `path` into `destination` with the default limits.
+pub fn extract(path: &Path, destination: &Path) -> Result<PathBuf> {
+    extract_with(path, destination, &ExtractLimits::default())
+}
+
+/// Extracts the archive at `path` into `destination`, detecting its format.
+///
+/// Nothing is written outside `destination`: entries with `..` or absolute paths, entries
+/// under a symlinked directory, and links pointing outside fail with `Error::ArchiveError`,
+/// as do archives exceeding `limits`.
+///
+/// Returns the archive's top-level directory when every entry lives under the same one, as
+/// JDK archives do (`jdk-17.0.8+7-jre/...`), and `destination` itself otherwise.
+pub fn extract_with(path: &Path, destination: &Path, limits: &ExtractLimits) -> Result<PathBuf> {
+    let mut file = std::fs::File::open(path)?;
+    let mut header = [0; 6];
+    let read = file.read(&mut header)?;
+    file.rewind()?;
+    let mut sandbox = Sandbox {
+        archive: path.display().to_string(),
+        destination,
+        limits,
+        entries: vec![],
+        written: 0,
+        traversed: vec![],
+    };
+    let format = ArchiveFormat::detect(&header[..read]).ok_or_else(|| sandbox.invalid("unknown archive format"))?;
+
+    std::fs::create_dir_all(destination)?;
+    match format {
+        ArchiveFormat::TarGz => sandbox.extract_tar(GzDecoder::new(file)),
+        ArchiveFormat::TarXz => sandbox.extract_tar(XzDecoder::new(file)),
+        ArchiveFormat::Zip => sandbox.extract_zip(file),
+    }?;
+    Ok(top_level(&sandbox.entries)
+        .map(|directory| destination.join(directory))
+        .unwrap_or_else(|| destination.to_owned()))
+}
+
+/// An archive entry path made relative and free of `.` and `..`, or `None` if it is
+/// absolute or climbs out of the archive root.
+pub fn safe_path(entry: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in entry.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Where a symlink at `entry` pointing to `target` ends up, relative to the archive root,
+/// or `None` if it leaves the archive. Every directory the target is looked up through is
+/// passed to `is_link`, and a target passing through a link is refused: the kernel would
+/// follow that link rather than the lexical path.
+pub(crate) fn resolve_link(entry: &Path, target: &Path, mut is_link: impl FnMut(&Path) -> bool) -> Option<PathBuf> {
+    let mut resolved = entry.parent().map(Path::to_owned).unwrap_or_default();
+    for component in target.components() {
+        if !resolved.as_os_str().is_empty() && is_link(&resolved) {
+            return None;
+        }
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Writes archive entries under `destination`, checking every path and the limits.
+struct Sandbox<'a> {
+    archive: String,
+    destination: &'a Path,
+    limits: &'a ExtractLimits,
+    entries: Vec<PathBuf>,
+    written: u64,
+    /// Directories that extracted symlinks are resolved through, which therefore must never
+    /// become symlinks themselves.
+    traversed: Vec<PathBuf>,
+}
+
+impl Sandbox<'_> {
+    fn invalid(&self, reason: impl Into<String>) -> Error {
+        Error::ArchiveError {
+            path: self.archive.clone(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Checks an entry's path and counts it, returning where it goes in `destination`.
+    fn entry(&mut self, path: &Path) -> Result<(PathBuf, PathBuf)> {
+        if self.entries.len() >= self.limits.max_entries {
+            return Err(self.invalid(format!("more than {} entries", self.limits.max_entries)));
+        }
+        let relative = safe_path(path)
+            .ok_or_else(|| self.invalid(format!("entry {} escapes the destination", path.display())))?;
+        // a symlink extracted earlier must not redirect later entries
+        let mut ancestor = self.destination.to_owned();
+        for component in relative.parent().into_iter().flat_map(Path::components) {
+            ancestor.push(component);
+            if is_symlink(&ancestor) {
+                return Err(self.invalid(format!("entry {} is inside a symlink", path.display())));
+            }
+        }
+        self.entries.push(relative.clone());
+        Ok((self.destination.join(&relative), relative))
+    }
+
+    fn create_parent(target: &Path) -> Result<()> {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, target: &Path, reader: &mut impl Read, mode: Option<u32>) -> Result<()> {
+        Self::create_parent(target)?;
+        if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(target)?;
+        }
+        let remaining = self.limits.max_total_size - self.written;
+        let copied = std::io::copy(&mut reader.take(remaining + 1), &mut std::fs::File::create(target)?)?;
+        if copied > remaining {
+            let _ = std::fs::remove_file(target);
+            return Err(self.invalid(format!("more than {} bytes when extracted", self.limits.max_total_size)));
+        }
+        self.written += copied;
+        set_mode(target, mode)
+    }
+
+    fn symlink(&mut self, target: &Path, relative: &Path, link: &Path) -> Result<()> {
+        let escapes = || self.invalid(format!("link {} -> {} escapes the destination", relative.display(), link.display()));
+        if self.traversed.iter().any(|directory| directory == relative) {
+            // an earlier link was checked assuming this is a plain directory
+            return Err(escapes());
+        }
+        let mut traversed = vec![];
+        let resolved = resolve_link(relative, link, |directory| {
+            traversed.push(directory.to_owned());
+            is_symlink(&self.destination.join(directory))
+        });
+        if resolved.is_none() {
+            return Err(escapes());
+        }
+        self.traversed.append(&mut traversed);
+        Self::create_parent(target)?;
+        if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(target)?;
+        }
+        symlink(link, target)
+    }
+
+    fn hard_link(&self, target: &Path, relative: &Path, link: &Path) -> Result<()> {
+        let escapes = || self.invalid(format!("link {} -> {} escapes the destination", relative.display(), link.display()));
+        let source = safe_path(link).ok_or_else(escapes)?;
+        // hard_link follows symlinks on the way to the source, and a hard link to a symlink
+        // would resolve relative to its new location
+        let mut ancestor = self.destination.to_owned();
+        for component in source.components() {
+            ancestor.push(component);
+            if is_symlink(&ancestor) {
+                return Err(escapes());
+            }
+        }
+        Self::create_parent(target)?;
+        if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(target)?;
+        }
+        std::fs::hard_link(self.destination.join(source), target)?;
+        Ok(())
+    }
+
+    fn extract_tar(&mut self, reader: impl Read) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(|e| self.invalid(e.to_string()))? {
+            let mut entry = entry.map_err(|e| self.invalid(e.to_string()))?;
+            let path = entry.path().map_err(|e| self.invalid(e.to_string()))?.into_owned();
+            let entry_type = entry.header().entry_type();
+            if !(entry_type.is_file() || entry_type.is_dir() || entry_type.is_symlink() || entry_type.is_hard_link()) {
+                // device files, fifos and extension headers have nothing to extract
+                continue;
+            }
+            let (target, relative) = self.entry(&path)?;
+            if entry_type.is_dir() {
+                std::fs::create_dir_all(&target)?;
+            } else if entry_type.is_symlink() || entry_type.is_hard_link() {
+                let link = entry
+                    .link_name()
+                    .map_err(|e| self.invalid(e.to_string()))?
+                    .ok_or_else(|| self.invalid(format!("link {} has no target", path.display())))?
+                    .into_owned();
+                if entry_type.is_symlink() {
+                    self.symlink(&target, &relative, &link)?;
+                } else {
+                    self.hard_link(&target, &relative, &link)?;
+                }
+            } else {
+                let mode = entry.header().mode().ok();
+                self.write_file(&target, &mut entry, mode)
+                    .map_err(|e| match e {
+                        Error::FileIOError(e) if e.kind() == std::io::ErrorKind::InvalidData => self.invalid(e.to_string()),
+                        e => e,
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unix permission bits stored in a zip are applied to the extracted files, and entries
+    /// stored as unix symlinks are recreated as links.
+    fn extract_zip(&mut self, reader: impl Read + Seek) -> Result<()> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(|e| self.invalid(e.to_string()))?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(|e| self.invalid(e.to_string()))?;
+            let path = PathBuf::from(entry.name());
+            let (target, relative) = self.entry(&path)?;
+            let mode = entry.unix_mode();
+            if entry.is_dir() {
+                std::fs::create_dir_all(&target)?;
+            } else if mode.is_some_and(|mode| mode & 0o170000 == 0o120000) {
+                let mut link = String::new();
+                entry.read_to_string(&mut link)?;
+                self.symlink(&target, &relative, Path::new(&link))?;
+            } else {
+                self.write_file(&target, &mut entry, mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn set_mode(target: &Path, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(target, std::fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_mode(_target: &Path, _mode: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink(link: &Path, target: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(link, target)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub(crate) fn symlink(link: &Path, target: &Path) -> Result<()> {
+    std::os::windows::fs::symlink_file(link, target)?;
+    Ok(())
+}
+
+/// The single directory every entry is nested in, if there is one.
+fn top_level(entries: &[PathBuf]) -> Option<PathBuf> {
+    let mut top = None;
+    let mut nested = false;
+    for entry in entries {
+        let mut components = entry.components().filter(|component| !matches!(component, Component::CurDir));
+        let Some(Component::Normal(first)) = components.next() else {
+            return None;
+        };
+        nested |= components.next().is_some();
+        match &top {
+            None => top = Some(first.to_owned()),
+            Some(top) if top == first => {}
+            Some(_) => return None,
+        }
+    }
+    top.filter(|_| nested).map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use super::{extract, extract_with, safe_path, ArchiveFormat, ExtractLimits};
+    use crate::error::Error;
+
+    fn tar(writer: impl Write) {
+        let mut builder = tar::Builder::new(writer);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o755);
+        builder.append_data(&mut header, "jdk-17/bin/java", &b"java"[..]).unwrap();
+        builder.into_inner().unwrap();
+    }
+
+    #[test]
+    fn extracts_every_format() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let gz = dir.join("jdk.tar.gz");
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&gz).unwrap(), flate2::Compression::fast());
+        tar(&mut encoder);
+        encoder.finish().unwrap();
+
+        let xz = dir.join("jdk.tar.xz");
+        let mut encoder = xz2::write::XzEncoder::new(std::fs::File::create(&xz).unwrap(), 1);
+        tar(&mut encoder);
+        encoder.finish().unwrap();
+
+        let zip = dir.join("jdk.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip).unwrap());
+        writer.add_directory("jdk-17/bin", Default::default()).unwrap();
+        writer
+            .start_file("jdk-17/bin/java", zip::write::FileOptions::default().unix_permissions(0o755))
+            .unwrap();
+        writer.write_all(b"java").unwrap();
+        writer.finish().unwrap();
+
+        for (archive, format) in [(gz, ArchiveFormat::TarGz), (xz, ArchiveFormat::TarXz), (zip, ArchiveFormat::Zip)] {
+            assert_eq!(ArchiveFormat::detect(&std::fs::read(&archive).unwrap()), Some(format));
+            let destination = dir.join(format!("{format:?}"));
+            let root = extract(&archive, &destination).unwrap();
+            assert_eq!(root, destination.join("jdk-17"));
+            assert_eq!(std::fs::read_to_string(root.join("bin/java")).unwrap(), "java");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(root.join("bin/java")).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o755, "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let archive = dir.join("jdk.rar");
+        std::fs::write(&archive, "Rar!").unwrap();
+        assert!(matches!(extract(&archive, &dir), Err(crate::error::Error::ArchiveError { .. })));
+    }
+
+    #[test]
+    fn normalizes_entry_paths() {
+        assert_eq!(safe_path(Path::new("./jdk/bin/java")), Some(PathBuf::from("jdk/bin/java")));
+        assert_eq!(safe_path(Path::new("jdk/../../etc/passwd")), None);
+        assert_eq!(safe_path(Path::new("/etc/passwd")), None);
+        assert_eq!(safe_path(Path::new(".")), None);
+    }
+
+    /// A raw tar entry, bypassing the path checks `tar::Builder` applies when writing.
+    fn raw_entry(builder: &mut tar::Builder<impl Write>, name: &str, entry_type: tar::EntryType, link: Option<&str>, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        if let Some(link) = link {
+            header.as_gnu_mut().unwrap().linkname[..link.len()].copy_from_slice(link.as_bytes());
+        }
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    /// Writes a tar.gz fixture with `entries` of (name, type, link target, contents).
+    fn malicious_tar(dir: &Path, name: &str, entries: &[(&str, tar::EntryType, Option<&str>, &[u8])]) -> PathBuf {
+        let path = dir.join(name);
+        let encoder = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, entry_type, link, data) in entries {
+            raw_entry(&mut builder, name, *entry_type, *link, data);
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn assert_rejected(archive: &Path, destination: &Path) {
+        match extract(archive, destination) {
+            Err(Error::ArchiveError { .. }) => {}
+            other => panic!("{} was not rejected: {:?}", archive.display(), other.map_err(|e| e.to_string())),
+        }
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        use tar::EntryType::{Regular, Symlink};
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let destination = dir.join("jdk");
+
+        let fixtures = [
+            malicious_tar(&dir, "parent.tar.gz", &[("../evil.txt", Regular, None, b"evil")]),
+            malicious_tar(&dir, "nested-parent.tar.gz", &[("jdk-17/../../evil.txt", Regular, None, b"evil")]),
+            malicious_tar(&dir, "absolute.tar.gz", &[("/tmp/liblauncher-evil.txt", Regular, None, b"evil")]),
+        ];
+        for fixture in &fixtures {
+            assert_rejected(fixture, &destination);
+        }
+        assert!(!dir.join("evil.txt").exists());
+
+        let zip = dir.join("parent.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip).unwrap());
+        writer.start_file("../evil.txt", Default::default()).unwrap();
+        writer.write_all(b"evil").unwrap();
+        writer.finish().unwrap();
+        assert_rejected(&zip, &destination);
+        assert!(!dir.join("evil.txt").exists());
+
+        // a link that stays inside is fine, but nothing may be written through it
+        let through_link = malicious_tar(&dir, "through-link.tar.gz", &[
+            ("jdk-17/lib", Symlink, Some(".."), b""),
+            ("jdk-17/lib/evil.txt", Regular, None, b"evil"),
+        ]);
+        assert_rejected(&through_link, &destination);
+        assert!(!destination.join("evil.txt").exists());
+    }
+
+    #[test]
+    fn rejects_links_outside_destination() {
+        use tar::EntryType::{Link, Regular, Symlink};
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let destination = dir.join("jdk");
+
+        let fixtures = [
+            malicious_tar(&dir, "symlink.tar.gz", &[("jdk-17/escape", Symlink, Some("../../outside"), b"")]),
+            malicious_tar(&dir, "absolute-symlink.tar.gz", &[("jdk-17/passwd", Symlink, Some("/etc/passwd"), b"")]),
+            malicious_tar(&dir, "hardlink.tar.gz", &[("jdk-17/passwd", Link, Some("/etc/passwd"), b"")]),
+            malicious_tar(&dir, "parent-hardlink.tar.gz", &[("jdk-17/passwd", Link, Some("../outside"), b"")]),
+            // each link stays inside on its own, but `d/a` is resolved through `d/b`
+            malicious_tar(&dir, "symlink-chain.tar.gz", &[
+                ("jdk-17/d/b", Symlink, Some(".."), b""),
+                ("jdk-17/d/a", Symlink, Some("b/../.."), b""),
+            ]),
+            malicious_tar(&dir, "reversed-symlink-chain.tar.gz", &[
+                ("jdk-17/d/a", Symlink, Some("b/../.."), b""),
+                ("jdk-17/d/b", Symlink, Some(".."), b""),
+            ]),
+            malicious_tar(&dir, "hardlink-through-symlink.tar.gz", &[
+                ("jdk-17/up", Symlink, Some(".."), b""),
+                ("jdk-17/passwd", Link, Some("jdk-17/up/jdk-17/up"), b""),
+            ]),
+        ];
+        for (i, fixture) in fixtures.iter().enumerate() {
+            assert_rejected(fixture, &dir.join(format!("jdk-{i}")));
+        }
+
+        let zip = dir.join("symlink.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip).unwrap());
+        writer.add_symlink("jdk-17/escape", "../../outside", Default::default()).unwrap();
+        writer.finish().unwrap();
+        assert_rejected(&zip, &destination);
+
+        let inside = malicious_tar(&dir, "inside.tar.gz", &[
+            ("jdk-17/lib/libjvm.so", Regular, None, b"jvm"),
+            ("jdk-17/bin/libjvm.so", Symlink, Some("../lib/libjvm.so"), b""),
+            ("jdk-17/lib/server.so", Link, Some("jdk-17/lib/libjvm.so"), b""),
+        ]);
+        let root = extract(&inside, &destination).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("bin/libjvm.so")).unwrap(), "jvm");
+        assert_eq!(std::fs::read_to_string(root.join("lib/server.so")).unwrap(), "jvm");
+    }
+
+    #[test]
+    fn enforces_limits() {
+        use tar::EntryType::Regular;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let archive = malicious_tar(&dir, "bomb.tar.gz", &[
+            ("a.txt", Regular, None, b"aaaa"),
+            ("b.txt", Regular, None, b"bbbb"),
+        ]);
+
+        let few_entries = ExtractLimits { max_entries: 1, ..Default::default() };
+        assert!(matches!(extract_with(&archive, &dir.join("entries"), &few_entries), Err(Error::ArchiveError { .. })));
+        let small = ExtractLimits { max_total_size: 6, ..Default::default() };
+        assert!(matches!(extract_with(&archive, &dir.join("size"), &small), Err(Error::ArchiveError { .. })));
+        assert!(!dir.join("size/b.txt").exists());
+        let enough = ExtractLimits { max_entries: 2, max_total_size: 8 };
+        assert_eq!(extract_with(&archive, &dir.join("ok"), &enough).unwrap(), dir.join("ok"));
+    }
+}
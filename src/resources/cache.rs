@@ -0,0 +1,230 @@
+use reqwest::{header, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::{http::client, error::{Error, Result}};
+
+use super::download::download_config;
+use super::hash::{check_sha1, sha1_hex};
+use super::mirror;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheMeta {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+fn meta_path(path: &str) -> String {
+    format!("{path}.meta")
+}
+
+async fn read_cached(path: &str, sha1: Option<&str>) -> Option<String> {
+    let body = tokio::fs::read_to_string(path).await.ok()?;
+    if let Some(expected) = sha1 {
+        if !sha1_hex(body.as_bytes()).eq_ignore_ascii_case(expected) {
+            warn!("cached {} does not match sha1 {}, ignoring it", path, expected);
+            return None;
+        }
+    }
+    Some(body)
+}
+
+async fn read_meta(path: &str) -> CacheMeta {
+    tokio::fs::read_to_string(meta_path(path))
+        .await
+        .ok()
+        .and_then(|meta| serde_json::from_str(&meta).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the cached copy of `url`, revalidating it with `ETag`/`Last-Modified` when online.
+/// When `sha1` is given, the cached and downloaded bodies must hash to it.
+pub async fn fetch_with_mode(url: &str, path: &str, sha1: Option<&str>, offline: bool) -> Result<String> {
+    if offline {
+        let body = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|_| Error::NotCached(path.to_owned()))?;
+        if let Some(expected) = sha1 {
+            check_sha1(path, body.as_bytes(), expected)?;
+        }
+        return Ok(body);
+    }
+
+    let mut last_error = None;
+    for candidate in mirror::candidates(url, &download_config().mirrors) {
+        match fetch_online(&candidate, path, sha1).await {
+            Ok(body) => return Ok(body),
+            Err(e) => {
+                warn!("could not fetch {}: {}", candidate, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap())
+}
+
+/// Fetches `url` through the on-disk cache at `path`, honouring the global offline mode, and
+/// parses it as JSON.
+pub async fn fetch_json<T: DeserializeOwned>(url: &str, path: &str, sha1: Option<&str>) -> Result<T> {
+    fetch_json_with_mode(url, path, sha1, crate::is_offline_mode()).await
+}
+
+/// Like `fetch_with_mode`, parsing the body as JSON. When online, a cached copy that doesn't
+/// parse, such as one truncated by an interrupted write, is dropped and fetched again in full
+/// instead of being revalidated.
+pub async fn fetch_json_with_mode<T: DeserializeOwned>(url: &str, path: &str, sha1: Option<&str>, offline: bool) -> Result<T> {
+    let body = fetch_with_mode(url, path, sha1, offline).await?;
+    match serde_json::from_str(&body) {
+        Ok(value) => Ok(value),
+        Err(e) if !offline => {
+            warn!("{} could not be parsed, fetching it again: {}", path, e);
+            let _ = tokio::fs::remove_file(meta_path(path)).await;
+            let _ = tokio::fs::remove_file(path).await;
+            Ok(serde_json::from_str(&fetch_with_mode(url, path, sha1, offline).await?)?)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes `contents` to a `.part` file next to `path` and renames it into place, so an
+/// interrupted write never leaves a truncated file at `path`.
+async fn write_replacing(path: &str, contents: &[u8]) -> Result<()> {
+    let part_path = format!("{path}.part");
+    tokio::fs::write(&part_path, contents).await?;
+    tokio::fs::rename(&part_path, path).await?;
+    Ok(())
+}
+
+async fn fetch_online(url: &str, path: &str, sha1: Option<&str>) -> Result<String> {
+    let cached = read_cached(path, sha1).await;
+    let mut request = client().get(url);
+    if cached.is_some() {
+        let meta = read_meta(path).await;
+        if let Some(etag) = meta.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = meta.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(body) = cached {
+            info!("{} not modified, using cached copy", url);
+            return Ok(body);
+        }
+    }
+    let response = response.error_for_status()?;
+
+    let header_value = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let meta = CacheMeta {
+        etag: header_value(header::ETAG),
+        last_modified: header_value(header::LAST_MODIFIED),
+    };
+    let body = response.text().await?;
+    if let Some(expected) = sha1 {
+        check_sha1(url, body.as_bytes(), expected)?;
+    }
+
+    if let Some(parent) = Path::new(path).parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // without its validators a body is fetched in full next time, never revalidated
+    let _ = tokio::fs::remove_file(meta_path(path)).await;
+    write_replacing(path, body.as_bytes()).await?;
+    write_replacing(&meta_path(path), serde_json::to_string(&meta)?.as_bytes()).await?;
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tempfile::TempDir;
+    use tracing_test::traced_test;
+    use warp::Filter;
+
+    use super::{fetch_json_with_mode, fetch_with_mode};
+    use crate::{error::Error, resources::hash::sha1_hex};
+
+    const BODY: &str = "{\"id\":\"1.19\"}";
+
+    fn serve_with_etag() -> (String, Arc<AtomicUsize>) {
+        let full_responses = Arc::new(AtomicUsize::new(0));
+        let counter = full_responses.clone();
+        let filter = warp::header::optional::<String>("if-none-match").map(move |etag: Option<String>| {
+            if etag.as_deref() == Some("\"v1\"") {
+                warp::http::Response::builder().status(304).body(String::new()).unwrap()
+            } else {
+                counter.fetch_add(1, Ordering::SeqCst);
+                warp::http::Response::builder().header("ETag", "\"v1\"").body(BODY.to_owned()).unwrap()
+            }
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{addr}/version.json"), full_responses)
+    }
+
+    fn temp_path(dir: &TempDir) -> String {
+        dir.path().join("version.json").to_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn revalidates_with_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, full_responses) = serve_with_etag();
+        let path = temp_path(&dir);
+        let sha1 = sha1_hex(BODY.as_bytes());
+
+        assert_eq!(fetch_with_mode(&url, &path, Some(&sha1), false).await.unwrap(), BODY);
+        assert_eq!(fetch_with_mode(&url, &path, Some(&sha1), false).await.unwrap(), BODY);
+        assert_eq!(full_responses.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn serves_offline_from_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, _) = serve_with_etag();
+        let path = temp_path(&dir);
+        let sha1 = sha1_hex(BODY.as_bytes());
+
+        assert!(matches!(fetch_with_mode(&url, &path, None, true).await, Err(Error::NotCached(_))));
+        fetch_with_mode(&url, &path, None, false).await.unwrap();
+        assert_eq!(fetch_with_mode("http://unreachable.invalid", &path, Some(&sha1), true).await.unwrap(), BODY);
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(matches!(
+            fetch_with_mode(&url, &path, Some(&sha1), true).await,
+            Err(Error::Sha1Mismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn refetches_unparseable_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, full_responses) = serve_with_etag();
+        let path = temp_path(&dir);
+        std::fs::write(&path, &BODY[..5]).unwrap();
+        std::fs::write(format!("{path}.meta"), r#"{"etag": "\"v1\""}"#).unwrap();
+
+        let details: serde_json::Value = fetch_json_with_mode(&url, &path, None, false).await.unwrap();
+        assert_eq!(details["id"], "1.19");
+        assert_eq!(full_responses.load(Ordering::SeqCst), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), BODY);
+        assert!(!std::path::Path::new(&format!("{path}.part")).exists());
+    }
+}
@@ -1,16 +1,26 @@
 //TODO: Fix, no like
 
 use crate::error::Error;
-use crate::{client, error::Result};
+use crate::{http::download_client, error::Result, path_with_launcher};
+use super::archive;
+use super::hash::{check_sha1, file_sha1, sha256_hex};
+use super::limits::Limiter;
+use super::mirror::{self, Mirror};
+use super::progress::{Progress, ProgressEvent};
+use super::retry::check_status;
+pub use super::retry::RetryPolicy;
 
 use async_trait::async_trait;
-use flate2::bufread::GzDecoder;
+use sha1::{Digest, Sha1};
 use futures::StreamExt;
-use tar::Archive;
-use std::{fs, path::Path};
+use glob::glob;
+use lazy_static::lazy_static;
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::Duration};
+use tokio::sync::OnceCell;
 use tokio::time::Instant;
-use tokio::{fs::File, io::AsyncWriteExt};
-use tracing::{error, info};
+use reqwest::{header, StatusCode};
+use tokio::{fs::{File, OpenOptions}, io::{AsyncReadExt, AsyncWriteExt}};
+use tracing::{error, info, warn};
 
 pub trait Downloadeable {
     fn download_info(&self) -> DownloadType;
@@ -19,15 +29,40 @@ pub trait Downloadeable {
 pub enum DownloadType {
     Simple(Download),
     SizeCheck(DownloadWithSizeCheck),
-    Archive(DownloadArchive)
+    HashCheck(DownloadWithHashCheck),
+    Archive(DownloadArchive),
+    Lzma(DownloadLzma),
+}
+
+/// How thoroughly already present files are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Size,
+    Hash,
 }
 
 impl DownloadType {
     pub async fn download(&self) -> Result<DownloadStatus> {
+        self.download_with(&Progress::none()).await
+    }
+
+    /// Downloads the file, reporting byte-level progress through `progress`.
+    pub async fn download_with(&self, progress: &Progress) -> Result<DownloadStatus> {
+        match self {
+            DownloadType::Simple(d) => d.download(progress).await,
+            DownloadType::SizeCheck(d) => d.download(progress).await,
+            DownloadType::HashCheck(d) => d.download(progress).await,
+            DownloadType::Archive(d) => d.download(progress).await,
+            DownloadType::Lzma(d) => d.download(progress).await,
+        }
+    }
+
+    pub fn expected_size(&self) -> Option<u64> {
         match self {
-            DownloadType::Simple(d) => d.download().await,
-            DownloadType::SizeCheck(d) => d.download().await,
-            DownloadType::Archive(d) => d.download().await,
+            DownloadType::SizeCheck(d) => Some(d.size as u64),
+            DownloadType::HashCheck(d) => Some(d.size as u64),
+            DownloadType::Lzma(d) => Some(d.compressed_size as u64),
+            DownloadType::Simple(_) | DownloadType::Archive(_) => None,
         }
     }
 
@@ -35,15 +70,20 @@ impl DownloadType {
         match self {
             DownloadType::Simple(d) => d,
             DownloadType::SizeCheck(d) => &d.download,
+            DownloadType::HashCheck(d) => &d.download,
             DownloadType::Archive(d) => &d.download,
+            DownloadType::Lzma(d) => &d.download,
         }
     }
 
-    pub fn size_check(self) -> Option<DownloadWithSizeCheck> {
-        return if let Self::SizeCheck(size_check) = self {
-            Some(size_check)
-        } else {
-            None
+    pub fn is_present(&self, verification: Verification) -> bool {
+        match (self, verification) {
+            (DownloadType::SizeCheck(d), _) => d.check_size(),
+            (DownloadType::HashCheck(d), Verification::Size) => d.check_size(),
+            (DownloadType::HashCheck(d), Verification::Hash) => d.check_hash(),
+            (DownloadType::Lzma(d), Verification::Size) => d.check_size(),
+            (DownloadType::Lzma(d), Verification::Hash) => d.check_hash(),
+            _ => Path::new(&self.inner().path).exists(),
         }
     }
 }
@@ -51,6 +91,59 @@ impl DownloadType {
 pub struct DownloadStatus {
     pub speed: f32,
     pub size: usize,
+    /// The file was already on disk and passed its check, so nothing was transferred.
+    pub already_present: bool,
+    /// For archives, the directory their contents ended up in.
+    pub extracted_to: Option<PathBuf>,
+}
+
+impl DownloadStatus {
+    fn already_present(size: usize) -> Self {
+        Self { speed: 0.0, size, already_present: true, extracted_to: None }
+    }
+}
+
+/// What happened to every item of a download job.
+#[derive(Debug, Default)]
+pub struct DownloadReport {
+    pub succeeded: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, Error)>,
+    /// Set when the job was cancelled before every item was downloaded.
+    pub cancelled: bool,
+}
+
+impl DownloadReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && !self.cancelled
+    }
+
+    pub fn record(&mut self, path: String, result: Result<DownloadStatus>) {
+        match result {
+            Ok(status) if status.already_present => self.skipped.push(path),
+            Ok(_) => self.succeeded.push(path),
+            Err(e) => self.failed.push((path, e)),
+        }
+    }
+
+    pub fn merge(&mut self, mut other: DownloadReport) {
+        self.succeeded.append(&mut other.succeeded);
+        self.skipped.append(&mut other.skipped);
+        self.failed.append(&mut other.failed);
+        self.cancelled |= other.cancelled;
+    }
+
+    /// Turns a cancelled report into `Error::Cancelled` and one with failures into
+    /// `Error::IncompleteDownload`.
+    pub fn into_result(self) -> Result<DownloadReport> {
+        if self.cancelled {
+            Err(Error::Cancelled)
+        } else if self.is_complete() {
+            Ok(self)
+        } else {
+            Err(Error::IncompleteDownload(self))
+        }
+    }
 }
 
 pub struct Download {
@@ -62,67 +155,441 @@ pub struct DownloadWithSizeCheck {
     pub size: usize
 }
 
+pub struct DownloadWithHashCheck {
+    pub download: Download,
+    pub size: usize,
+    pub sha1: String,
+}
+
 pub struct DownloadArchive {
     pub download: Download,
     pub destination: String,
+    /// Published SHA-256 of the archive, checked before anything is extracted.
+    pub sha256: Option<String>,
 }
 
-pub fn is_downloaded(items: &[impl Downloadeable]) -> bool {
-    for item in items.iter() {
-        if !item.download_info().size_check().unwrap().check_size() {
-            return false;
-        }
-    }
+/// A file served LZMA-compressed from `download.url` and stored decompressed at `download.path`.
+/// The compressed and decompressed data are each checked against their own size and hash.
+pub struct DownloadLzma {
+    pub download: Download,
+    pub compressed_size: usize,
+    pub compressed_sha1: String,
+    pub size: usize,
+    pub sha1: String,
+}
+
+pub fn is_downloaded(items: &[impl Downloadeable], verification: Verification) -> bool {
+    items.iter().all(|item| item.download_info().is_present(verification))
+}
 
-    return true;
+/// Paths of the items that are missing or fail verification.
+pub fn find_invalid(items: &[impl Downloadeable], verification: Verification) -> Vec<String> {
+    items
+        .iter()
+        .map(|item| item.download_info())
+        .filter(|download| !download.is_present(verification))
+        .map(|download| download.inner().path.clone())
+        .collect()
 }
 
 pub fn log_download(item: &Download, result: &Result<DownloadStatus>) {
     match result {
+        Ok(status) if status.already_present => info!("already present: {}", item.path),
         Ok(_) => info!("download success: {}", item.path),
         Err(e) => error!("download {} failed: {}", item.path, e),
     }
 }
 
-pub async fn download_collection<F>(collection: &[impl Downloadeable], f: F)
-    where F: FnOnce(&Download, &Result<DownloadStatus>) + Clone {
+/// Total number of files and known bytes of a set of downloads, for `ProgressEvent::JobStarted`.
+pub fn totals<'a>(downloads: impl IntoIterator<Item = &'a DownloadType>) -> (usize, u64) {
+    downloads
+        .into_iter()
+        .fold((0, 0), |(files, bytes), download| (files + 1, bytes + download.expected_size().unwrap_or(0)))
+}
+
+/// Downloads a collection as one job, from `JobStarted` to `JobDone`.
+pub async fn download_collection(collection: &[impl Downloadeable], progress: &Progress) -> DownloadReport {
+    let (total_files, total_bytes) = totals(&collection.iter().map(|item| item.download_info()).collect::<Vec<_>>());
+    progress.send(ProgressEvent::JobStarted { total_files, total_bytes });
+    let report = download_items(collection, progress).await;
+    progress.send(ProgressEvent::JobDone { failed: report.failed.len() });
+    report
+}
+
+/// Downloads every item, reporting per-file events. Once `progress` is cancelled no new
+/// items are started and the report is marked cancelled.
+pub(crate) async fn download_items(collection: &[impl Downloadeable], progress: &Progress) -> DownloadReport {
     let tasks = tokio_stream::iter(collection)
-        .map(|item| {
-            let f = f.to_owned();
-            let item = item.to_owned();
-            async move {
-                let download = item.download_info();
-                let status = download.download().await;
-                f(download.inner(), &status);
-            }
-        }).buffer_unordered(5);
+        .take_while(|_| futures::future::ready(!progress.is_cancelled()))
+        .map(|item| async move {
+            let download = item.download_info();
+            let result = download_item(&download, progress).await;
+            (download.inner().path.clone(), result)
+        }).buffer_unordered(download_config().concurrency.max(1));
+
+    let mut report = tasks
+        .fold(DownloadReport::default(), |mut report, (path, result)| {
+            report.record(path, result);
+            futures::future::ready(report)
+        })
+        .await;
+    report.cancelled = progress.is_cancelled();
+    report
+}
 
-    tasks.collect::<Vec<_>>().await;
+/// Downloads a single file, logging it and reporting `FileDone`, `FileSkipped` or `FileFailed`.
+pub(crate) async fn download_item(download: &DownloadType, progress: &Progress) -> Result<DownloadStatus> {
+    let path = download.inner().path.clone();
+    let status = download.download_with(progress).await;
+    log_download(download.inner(), &status);
+    match &status {
+        Ok(status) if status.already_present => progress.send(ProgressEvent::FileSkipped { path, size: Some(status.size as u64) }),
+        Ok(status) => progress.send(ProgressEvent::FileDone { path, size: status.size as u64 }),
+        Err(e) => progress.send(ProgressEvent::FileFailed { path, error: e.to_string() }),
+    }
+    status
 }
 
 impl Download {
-    async fn download(&self) -> Result<DownloadStatus> {
-        let mut body_stream = client.get(&self.url).send().await?.bytes_stream();
-    
+    async fn download(&self, progress: &Progress) -> Result<DownloadStatus> {
+        self.download_checked(None, None, progress).await
+    }
+
+    pub fn part_path(&self) -> String {
+        format!("{}.part", self.path)
+    }
+
+    /// Whether the file is on disk with `size` bytes.
+    fn has_size(&self, size: usize) -> bool {
+        fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() == size as u64)
+    }
+
+    /// Whether the file is on disk with `size` bytes hashing to `sha1`.
+    fn has_sha1(&self, size: usize, sha1: &str) -> bool {
+        self.has_size(size) && file_sha1(&self.path).is_ok_and(|actual| actual.eq_ignore_ascii_case(sha1))
+    }
+
+    async fn download_checked(&self, size: Option<usize>, sha1: Option<&str>, progress: &Progress) -> Result<DownloadStatus> {
+        clean_partial_downloads_once().await;
+        self.download_with_mirrors(size, sha1, &download_config(), progress).await
+    }
+
+    /// Tries every configured mirror for this URL in order, then the official URL,
+    /// moving on when a mirror fails or serves a file that doesn't match its hash.
+    async fn download_with_mirrors(&self, size: Option<usize>, sha1: Option<&str>, config: &DownloadConfig, progress: &Progress) -> Result<DownloadStatus> {
+        let mut candidates = mirror::candidates(&self.url, &config.mirrors);
+        let official = candidates.pop().unwrap();
+        for url in candidates {
+            let download = Download { path: self.path.clone(), url };
+            match download.download_with_retry(size, sha1, config, progress).await {
+                Ok(status) => return Ok(status),
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(e) => {
+                    warn!("mirror {} failed, falling back: {}", download.url, e);
+                    download.discard_part().await;
+                }
+            }
+        }
+        Download { path: self.path.clone(), url: official }
+            .download_with_retry(size, sha1, config, progress)
+            .await
+    }
+
+    /// Retries transient failures according to `policy`. If more than one attempt was made,
+    /// the final error is `Error::DownloadFailed` with every attempt's error.
+    ///
+    /// Cancelling `progress` aborts the attempt or the wait before the next one,
+    /// deletes the partial download and fails with `Error::Cancelled`.
+    async fn download_with_retry(&self, size: Option<usize>, sha1: Option<&str>, config: &DownloadConfig, progress: &Progress) -> Result<DownloadStatus> {
+        let result = tokio::select! {
+            biased;
+            _ = progress.cancelled() => Err(Error::Cancelled),
+            result = self.retry_download(size, sha1, config, progress) => result,
+        };
+        if matches!(result, Err(Error::Cancelled)) {
+            info!("download of {} cancelled", self.url);
+            self.discard_part().await;
+        }
+        result
+    }
+
+    async fn retry_download(&self, size: Option<usize>, sha1: Option<&str>, config: &DownloadConfig, progress: &Progress) -> Result<DownloadStatus> {
+        let policy = &config.retry;
+        let mut attempts = vec![];
+        loop {
+            let error = match self.try_download(size, sha1, config, progress).await {
+                Ok(status) => return Ok(status),
+                Err(e) => e,
+            };
+            let delay = policy.delay_after(attempts.len() as u32 + 1, &error);
+            attempts.push(error);
+            match delay {
+                Some(delay) => {
+                    warn!("download {} failed (attempt {}), retrying in {:?}: {}", self.url, attempts.len(), delay, attempts.last().unwrap());
+                    tokio::time::sleep(delay).await;
+                }
+                None if attempts.len() == 1 => return Err(attempts.pop().unwrap()),
+                None => return Err(Error::DownloadFailed { url: self.url.clone(), attempts }),
+            }
+        }
+    }
+
+    fn part_meta_path(&self) -> String {
+        format!("{}.part.meta", self.path)
+    }
+
+    /// Downloads into a `.part` file next to the destination, hashing while streaming,
+    /// and only renames it into place once the size and hash checks pass.
+    ///
+    /// A `.part` file left by an interrupted attempt is resumed with `Range`/`If-Range`
+    /// when its `ETag` or `Last-Modified` validator was recorded in `.part.meta`.
+    async fn try_download(&self, size: Option<usize>, sha1: Option<&str>, config: &DownloadConfig, progress: &Progress) -> Result<DownloadStatus> {
         if let Some(parent) = Path::new(&self.path).parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
-        let mut file = File::create(&self.path).await?;
-    
-        let mut size = 0;
+        let part_path = self.part_path();
+
+        let resume_from = self.resumable_length().await;
+        let mut request = download_client().get(&self.url);
+        if let Some((length, validator)) = &resume_from {
+            request = request
+                .header(header::RANGE, format!("bytes={length}-"))
+                .header(header::IF_RANGE, validator);
+        }
+        let limiter = limiter();
+        let permit = limiter.acquire(&self.url).await;
+        let mut response = with_timeout(config.connect_timeout, &self.url, request.send()).await??;
+        // only a resume can be out of range; anything else is an error like any other status
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && resume_from.is_some() {
+            drop(permit);
+            self.discard_part().await;
+            return Box::pin(self.try_download(size, sha1, config, progress)).await;
+        }
+        response = check_status(response)?;
+        if response.status() == StatusCode::PARTIAL_CONTENT {
+            let expected_start = resume_from.as_ref().map_or(0, |(length, _)| *length);
+            if content_range_start(&response) != Some(expected_start) {
+                drop(permit);
+                if resume_from.is_none() {
+                    return Err(Error::HttpStatus { url: self.url.clone(), status: 206, retry_after: None });
+                }
+                warn!("{} answered a resume from byte {} with another range, restarting", self.url, expected_start);
+                self.discard_part().await;
+                return Box::pin(self.try_download(size, sha1, config, progress)).await;
+            }
+        }
+
+        let mut hasher = Sha1::new();
+        let (mut file, mut downloaded) = match resume_from {
+            Some((length, _)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+                info!("resuming {} from byte {}", self.url, length);
+                let mut existing = File::open(&part_path).await?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    let read = existing.read(&mut buffer).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buffer[..read]);
+                }
+                (OpenOptions::new().append(true).open(&part_path).await?, length as usize)
+            }
+            _ => {
+                let validator = [header::ETAG, header::LAST_MODIFIED]
+                    .iter()
+                    .find_map(|name| response.headers().get(name).and_then(|v| v.to_str().ok()));
+                match validator {
+                    Some(validator) => tokio::fs::write(self.part_meta_path(), validator).await?,
+                    None => { let _ = tokio::fs::remove_file(self.part_meta_path()).await; }
+                }
+                (File::create(&part_path).await?, 0)
+            }
+        };
+
+        let mut body_stream = response.bytes_stream();
+        let mut transferred = 0;
         let instant = Instant::now();
-        while let Some(chunk) = body_stream.next().await {
+        while let Some(chunk) = with_timeout(config.read_timeout, &self.url, body_stream.next()).await? {
             let chunk = chunk?;
-            size += chunk.len();
+            limiter.throttle(chunk.len()).await;
+            transferred += chunk.len();
+            hasher.update(&chunk);
             file.write_all(&chunk).await?;
+            progress.send(ProgressEvent::FileProgress {
+                path: self.path.clone(),
+                downloaded: (downloaded + transferred) as u64,
+                total: size.map(|size| size as u64),
+            });
+        }
+        downloaded += transferred;
+        file.sync_all().await?;
+        drop(file);
+        let speed = transferred as f32 / instant.elapsed().as_secs_f32();
+
+        if let Some(expected) = size {
+            if downloaded != expected {
+                self.discard_part().await;
+                return Err(Error::SizeMismatch {
+                    path: self.path.clone(),
+                    expected,
+                    actual: downloaded,
+                });
+            }
         }
-        let speed = size as f32 / instant.elapsed().as_secs_f32();
+        if let Some(expected) = sha1 {
+            let actual = hex::encode(hasher.finalize());
+            if !actual.eq_ignore_ascii_case(expected) {
+                self.discard_part().await;
+                return Err(Error::Sha1Mismatch {
+                    path: self.path.clone(),
+                    expected: expected.to_owned(),
+                    actual,
+                });
+            }
+        }
+        tokio::fs::rename(&part_path, &self.path).await?;
+        let _ = tokio::fs::remove_file(self.part_meta_path()).await;
     
-        Ok(DownloadStatus { speed, size })
+        Ok(DownloadStatus { speed, size: downloaded, already_present: false, extracted_to: None })
+    }
+
+    /// Length and validator of a `.part` file that can be resumed.
+    async fn resumable_length(&self) -> Option<(u64, String)> {
+        let validator = tokio::fs::read_to_string(self.part_meta_path()).await.ok()?;
+        let length = tokio::fs::metadata(self.part_path()).await.ok()?.len();
+        (length > 0).then_some((length, validator))
+    }
+
+    async fn discard_part(&self) {
+        let _ = tokio::fs::remove_file(self.part_path()).await;
+        let _ = tokio::fs::remove_file(self.part_meta_path()).await;
     }
     
 }
 
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('-')?
+        .0
+        .parse()
+        .ok()
+}
+
+/// Settings shared by every download the launcher makes: assets, libraries, client, server and JDK.
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    pub retry: RetryPolicy,
+    /// Maximum number of files downloaded at once, across every running job.
+    pub concurrency: usize,
+    pub max_connections_per_host: Option<usize>,
+    /// Overall bandwidth cap in bytes per second.
+    pub bandwidth_limit: Option<u64>,
+    /// How long to wait for the response headers.
+    pub connect_timeout: Option<Duration>,
+    /// How long the body may stall before the attempt is abandoned.
+    pub read_timeout: Option<Duration>,
+    /// Mirrors tried, in order, before the official hosts.
+    pub mirrors: Vec<Mirror>,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            concurrency: 5,
+            max_connections_per_host: None,
+            bandwidth_limit: None,
+            connect_timeout: Some(Duration::from_secs(30)),
+            read_timeout: Some(Duration::from_secs(60)),
+            mirrors: vec![],
+        }
+    }
+}
+
+lazy_static! {
+    static ref DOWNLOAD_CONFIG: RwLock<(DownloadConfig, Arc<Limiter>)> = {
+        let config = DownloadConfig::default();
+        let limiter = Arc::new(Limiter::new(&config));
+        RwLock::new((config, limiter))
+    };
+}
+
+/// Replaces the download settings. Downloads already in flight keep the old limits.
+pub fn set_download_config(config: DownloadConfig) {
+    let limiter = Arc::new(Limiter::new(&config));
+    *DOWNLOAD_CONFIG.write().unwrap() = (config, limiter);
+}
+
+pub fn download_config() -> DownloadConfig {
+    DOWNLOAD_CONFIG.read().unwrap().0.clone()
+}
+
+fn limiter() -> Arc<Limiter> {
+    DOWNLOAD_CONFIG.read().unwrap().1.clone()
+}
+
+async fn with_timeout<T>(timeout: Option<Duration>, url: &str, future: impl std::future::Future<Output = T>) -> Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| Error::Timeout(url.to_owned())),
+        None => Ok(future.await),
+    }
+}
+
+/// How long a `.part` file has to go untouched before it counts as abandoned. Downloads in
+/// progress, in this or another launcher, write to theirs far more often than this.
+const STALE_PART_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Removes `.part` files left under the launcher root by an interrupted run that can't be
+/// resumed because no validator was recorded for them, along with orphaned `.part.meta` files.
+/// Only files untouched for `STALE_PART_AGE` are removed.
+pub fn clean_partial_downloads() -> Result<usize> {
+    clean_stale_parts(Path::new(&path_with_launcher("")), STALE_PART_AGE)
+}
+
+fn clean_stale_parts(root: &Path, age: Duration) -> Result<usize> {
+    let root = glob::Pattern::escape(&root.to_string_lossy());
+    let is_stale = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed >= age))
+    };
+    let mut removed = 0;
+    for part in glob(&format!("{root}/**/*.part"))?.flatten() {
+        let mut meta = part.clone().into_os_string();
+        meta.push(".meta");
+        if !Path::new(&meta).exists() && is_stale(&part) {
+            fs::remove_file(&part)?;
+            removed += 1;
+        }
+    }
+    for meta in glob(&format!("{root}/**/*.part.meta"))?.flatten() {
+        if !meta.with_extension("").exists() && is_stale(&meta) {
+            fs::remove_file(&meta)?;
+        }
+    }
+    Ok(removed)
+}
+
+async fn clean_partial_downloads_once() {
+    static CLEANED: OnceCell<()> = OnceCell::const_new();
+    CLEANED
+        .get_or_init(|| async {
+            match tokio::task::spawn_blocking(clean_partial_downloads).await {
+                Ok(Ok(removed)) if removed > 0 => info!("removed {} partial downloads", removed),
+                Ok(Err(e)) => error!("could not clean partial downloads: {}", e),
+                _ => {}
+            }
+        })
+        .await;
+}
+
 impl DownloadWithSizeCheck {
     pub fn check_size(&self) -> bool {
         let path_str = &self.download.path;
@@ -136,31 +603,103 @@ impl DownloadWithSizeCheck {
         };
     }
 
-    async fn download(&self) -> Result<DownloadStatus> {
+    async fn download(&self, progress: &Progress) -> Result<DownloadStatus> {
         if self.check_size() {
-            return Err(Error::FileExists(self.download.path.to_owned()))
+            return Ok(DownloadStatus::already_present(self.size))
         }    
         
-        Ok(self.download.download().await?)
+        self.download.download_checked(Some(self.size), None, progress).await
+    }
+}
+
+impl DownloadWithHashCheck {
+    pub fn check_size(&self) -> bool {
+        self.download.has_size(self.size)
+    }
+
+    pub fn check_hash(&self) -> bool {
+        self.download.has_sha1(self.size, &self.sha1)
+    }
+
+    async fn download(&self, progress: &Progress) -> Result<DownloadStatus> {
+        if self.check_hash() {
+            return Ok(DownloadStatus::already_present(self.size))
+        }
+
+        self.download.download_checked(Some(self.size), Some(&self.sha1), progress).await
+    }
+}
+
+impl DownloadLzma {
+    pub fn check_size(&self) -> bool {
+        self.download.has_size(self.size)
+    }
+
+    pub fn check_hash(&self) -> bool {
+        self.download.has_sha1(self.size, &self.sha1)
+    }
+
+    async fn download(&self, progress: &Progress) -> Result<DownloadStatus> {
+        if self.check_hash() {
+            return Ok(DownloadStatus::already_present(self.size))
+        }
+
+        let compressed = Download {
+            path: format!("{}.lzma", self.download.path),
+            url: self.download.url.clone(),
+        };
+        let status = compressed
+            .download_checked(Some(self.compressed_size), Some(&self.compressed_sha1), progress)
+            .await?;
+        let data = tokio::fs::read(&compressed.path).await?;
+        tokio::fs::remove_file(&compressed.path).await?;
+        let decompressed = tokio::task::spawn_blocking(move || {
+            let mut decompressed = Vec::new();
+            lzma_rs::lzma_decompress(&mut data.as_slice(), &mut decompressed).map(|_| decompressed)
+        }).await.unwrap().map_err(|e| Error::ArchiveError {
+            path: compressed.path.clone(),
+            reason: e.to_string(),
+        })?;
+        check_sha1(&self.download.path, &decompressed, &self.sha1)?;
+        // like any other download, only the complete file is ever moved into place
+        let part_path = self.download.part_path();
+        let mut file = File::create(&part_path).await?;
+        file.write_all(&decompressed).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&part_path, &self.download.path).await?;
+
+        Ok(DownloadStatus { speed: status.speed, size: self.size, already_present: false, extracted_to: None })
     }
 }
 
 impl DownloadArchive {
-    async fn download(&self) -> Result<DownloadStatus> {
-        let status = self.download.download().await?;
+    async fn download(&self, progress: &Progress) -> Result<DownloadStatus> {
+        let status = self.download.download(progress).await?;
+        if progress.is_cancelled() {
+            tokio::fs::remove_file(&self.download.path).await?;
+            return Err(Error::Cancelled);
+        }
         let file = tokio::fs::read(&self.download.path).await?;
-        let destination = self.destination.clone();
-        tokio::task::spawn_blocking(move || {
-            
-            let compressed = GzDecoder::new(file.as_slice());
-            let mut archive = Archive::new(compressed);
-            archive.unpack(&destination)
-        }).await.unwrap()?;
+        if let Some(expected) = &self.sha256 {
+            let actual = sha256_hex(&file);
+            if !actual.eq_ignore_ascii_case(expected) {
+                tokio::fs::remove_file(&self.download.path).await?;
+                return Err(Error::Sha256Mismatch {
+                    path: self.download.path.clone(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        let archive = PathBuf::from(&self.download.path);
+        let destination = PathBuf::from(&self.destination);
+        let extracted_to = tokio::task::spawn_blocking(move || archive::extract(&archive, &destination))
+            .await
+            .unwrap()?;
         tokio::fs::remove_file(&self.download.path).await?;
-        
-        
-        
-        Ok(status)
+
+        Ok(DownloadStatus { extracted_to: Some(extracted_to), ..status })
     }
 }
 
@@ -169,7 +708,13 @@ impl DownloadArchive {
 #[cfg(test)]
 mod tests {
     use crate::{resources::version::get_available_versions, error};
-    use super::{Download, DownloadStatus};
+    use crate::error::Error;
+    use crate::resources::progress::{Progress, ProgressEvent};
+    use super::{Download, DownloadStatus, DownloadType, DownloadWithHashCheck, DownloadConfig, RetryPolicy, Verification};
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use warp::Filter;
     use tracing::{info, error};
     use tracing_test::traced_test;
 
@@ -179,7 +724,7 @@ mod tests {
     #[traced_test]
     async fn download_assets() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         let details = latest.get_details().await.unwrap();
     }
 
@@ -187,10 +732,363 @@ mod tests {
     #[traced_test]
     async fn download_libraries() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         let details = latest.get_details().await.unwrap();
 
         
-        super::download_collection(&details.libraries, |_, _| {}).await;
+        super::download_collection(&details.library_artifacts(), &Progress::none()).await;
+    }
+
+    fn serve(body: &'static str) -> String {
+        let (addr, server) = warp::serve(warp::any().map(move || body)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{addr}/file")
+    }
+
+    fn temp_path(dir: &TempDir, name: &str) -> String {
+        dir.path().join(name).to_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn rejects_hash_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = serve("corrupted");
+        let download = DownloadWithHashCheck {
+            download: Download { path: temp_path(&dir, "mismatch.jar"), url },
+            size: 9,
+            sha1: String::from("a9993e364706816aba3e25717850c26c9cd0d89d"),
+        };
+
+        let result = DownloadType::HashCheck(download).download().await;
+        match result {
+            Err(Error::Sha1Mismatch { expected, actual, path }) => {
+                assert_eq!(expected, "a9993e364706816aba3e25717850c26c9cd0d89d");
+                assert_eq!(actual, crate::resources::hash::sha1_hex(b"corrupted"));
+                assert!(!std::path::Path::new(&path).exists());
+            }
+            _ => panic!("expected a sha1 mismatch"),
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn verifies_by_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = serve("abc");
+        let download = DownloadType::HashCheck(DownloadWithHashCheck {
+            download: Download { path: temp_path(&dir, "verified.jar"), url },
+            size: 3,
+            sha1: String::from("a9993e364706816aba3e25717850c26c9cd0d89d"),
+        });
+
+        download.download().await.unwrap();
+        assert!(download.is_present(Verification::Hash));
+        assert!(!std::path::Path::new(&download.inner().part_path()).exists());
+
+        std::fs::write(&download.inner().path, "abd").unwrap();
+        assert!(download.is_present(Verification::Size));
+        assert!(!download.is_present(Verification::Hash));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn keeps_truncated_download_out_of_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = serve("ab");
+        let download = Download { path: temp_path(&dir, "truncated.jar"), url };
+
+        let result = download.download_with_retry(Some(3), None, &no_retries(), &Progress::none()).await;
+        assert!(matches!(result, Err(Error::SizeMismatch { expected: 3, actual: 2, .. })));
+        assert!(!std::path::Path::new(&download.path).exists());
+        assert!(!std::path::Path::new(&download.part_path()).exists());
+    }
+
+    fn fast_retries() -> DownloadConfig {
+        DownloadConfig {
+            retry: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn no_retries() -> DownloadConfig {
+        DownloadConfig { retry: RetryPolicy::no_retries(), ..Default::default() }
+    }
+
+    fn serve_statuses(statuses: Vec<u16>) -> String {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let filter = warp::any().map(move || {
+            let request = requests.fetch_add(1, Ordering::SeqCst);
+            let status = statuses.get(request).copied().unwrap_or(200);
+            warp::http::Response::builder().status(status).body("abc").unwrap()
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{addr}/file")
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn retries_transient_statuses() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = Download { path: temp_path(&dir, "retried.jar"), url: serve_statuses(vec![503, 429]) };
+        download.download_with_retry(Some(3), None, &fast_retries(), &Progress::none()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), "abc");
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn rejects_unrequested_range_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = Download { path: temp_path(&dir, "unsatisfiable.jar"), url: serve_statuses(vec![416]) };
+        let result = download.download_with_retry(Some(3), None, &no_retries(), &Progress::none()).await;
+        assert!(matches!(result, Err(Error::HttpStatus { status: 416, .. })));
+        assert!(!std::path::Path::new(&download.path).exists());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn does_not_save_error_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = Download { path: temp_path(&dir, "missing.jar"), url: serve_statuses(vec![404]) };
+        let result = download.download_with_retry(None, None, &fast_retries(), &Progress::none()).await;
+        assert!(matches!(result, Err(Error::HttpStatus { status: 404, .. })));
+        assert!(!std::path::Path::new(&download.path).exists());
+
+        let download = Download { path: temp_path(&dir, "unavailable.jar"), url: serve_statuses(vec![500, 502, 503]) };
+        match download.download_with_retry(None, None, &fast_retries(), &Progress::none()).await {
+            Err(Error::DownloadFailed { attempts, .. }) => assert_eq!(attempts.len(), 3),
+            _ => panic!("expected every attempt to fail"),
+        }
+    }
+
+    const CONTENT: &str = "0123456789abcdefghij";
+
+    /// Serves `CONTENT` with an ETag, answering `Range` requests only if `ranges` is set.
+    fn serve_ranges(ranges: bool) -> (String, Arc<std::sync::Mutex<Vec<Option<String>>>>) {
+        serve_shifted_ranges(ranges, 0)
+    }
+
+    /// Like `serve_ranges`, but answers ranges `shift` bytes earlier than requested.
+    fn serve_shifted_ranges(ranges: bool, shift: usize) -> (String, Arc<std::sync::Mutex<Vec<Option<String>>>>) {
+        let seen_ranges = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = seen_ranges.clone();
+        let filter = warp::header::optional::<String>("range").map(move |range: Option<String>| {
+            seen.lock().unwrap().push(range.clone());
+            let start = range
+                .filter(|_| ranges)
+                .and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok())
+                .map(|start| start - shift);
+            let builder = warp::http::Response::builder().header("ETag", "\"v1\"");
+            match start {
+                Some(start) => builder
+                    .status(206)
+                    .header("Content-Range", format!("bytes {}-{}/{}", start, CONTENT.len() - 1, CONTENT.len()))
+                    .body(CONTENT[start..].to_owned()),
+                None => builder.body(CONTENT.to_owned()),
+            }
+            .unwrap()
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{addr}/jdk.tar.gz"), seen_ranges)
+    }
+
+    fn interrupted_download(dir: &TempDir, name: &str, url: String) -> Download {
+        let download = Download { path: temp_path(dir, name), url };
+        std::fs::write(download.part_path(), &CONTENT[..8]).unwrap();
+        std::fs::write(download.part_meta_path(), "\"v1\"").unwrap();
+        download
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn resumes_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, seen_ranges) = serve_ranges(true);
+        let download = interrupted_download(&dir, "resumed.tar.gz", url);
+        let sha1 = crate::resources::hash::sha1_hex(CONTENT.as_bytes());
+
+        let status = download.download_with_retry(Some(CONTENT.len()), Some(&sha1), &no_retries(), &Progress::none()).await.unwrap();
+        assert_eq!(status.size, CONTENT.len());
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), CONTENT);
+        assert_eq!(*seen_ranges.lock().unwrap(), [Some(String::from("bytes=8-"))]);
+        assert!(!std::path::Path::new(&download.part_meta_path()).exists());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn restarts_when_ranges_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, seen_ranges) = serve_ranges(false);
+        let download = interrupted_download(&dir, "restarted.tar.gz", url);
+        let sha1 = crate::resources::hash::sha1_hex(CONTENT.as_bytes());
+
+        download.download_with_retry(Some(CONTENT.len()), Some(&sha1), &no_retries(), &Progress::none()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), CONTENT);
+        assert_eq!(seen_ranges.lock().unwrap().len(), 1);
+    }
+
+    struct TestFile(String, String);
+
+    impl super::Downloadeable for TestFile {
+        fn download_info(&self) -> DownloadType {
+            DownloadType::HashCheck(DownloadWithHashCheck {
+                download: Download { path: self.0.clone(), url: self.1.clone() },
+                size: 3,
+                sha1: String::from("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            })
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn reports_job_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = serve("abc");
+        let present = temp_path(&dir, "present.bin");
+        std::fs::write(&present, "abc").unwrap();
+        let files = [TestFile(temp_path(&dir, "progress.bin"), url.clone()), TestFile(present.clone(), url)];
+        let _ = std::fs::remove_file(&files[0].0);
+
+        let (progress, mut events) = Progress::channel();
+        let report = super::download_collection(&files, &progress).await;
+        assert!(report.is_complete());
+        assert_eq!(report.succeeded.len(), 1);
+        assert_eq!(report.succeeded[0], files[0].0);
+        assert_eq!(report.skipped, vec![present.clone()]);
+        drop(progress);
+
+        let mut received = vec![];
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+        assert_eq!(received.first(), Some(&ProgressEvent::JobStarted { total_files: 2, total_bytes: 6 }));
+        assert_eq!(received.last(), Some(&ProgressEvent::JobDone { failed: 0 }));
+        assert!(received.contains(&ProgressEvent::FileProgress { path: files[0].0.clone(), downloaded: 3, total: Some(3) }));
+        assert!(received.contains(&ProgressEvent::FileDone { path: files[0].0.clone(), size: 3 }));
+        assert!(received.contains(&ProgressEvent::FileSkipped { path: present, size: Some(3) }));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn falls_back_from_bad_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let official = serve("abc");
+        let mirror = serve("not abc");
+        let config = DownloadConfig {
+            mirrors: vec![crate::resources::mirror::Mirror::new("broken").rewrite(&official, &mirror)],
+            ..no_retries()
+        };
+        let download = Download { path: temp_path(&dir, "mirrored.jar"), url: official };
+
+        download
+            .download_with_mirrors(Some(3), Some("a9993e364706816aba3e25717850c26c9cd0d89d"), &config, &Progress::none())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), "abc");
+    }
+
+    /// Sends the first bytes of a file, then stalls as if the connection were slow.
+    fn serve_stalled() -> String {
+        let filter = warp::any().map(|| {
+            let first = futures::stream::iter([Ok::<_, std::io::Error>("partial")]);
+            warp::http::Response::new(warp::hyper::Body::wrap_stream(futures::StreamExt::chain(first, futures::stream::pending())))
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{addr}/file")
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn cancels_in_flight_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = serve_stalled();
+        let files = [TestFile(temp_path(&dir, "cancelled.bin"), url.clone()), TestFile(temp_path(&dir, "also-cancelled.bin"), url)];
+        let token = tokio_util::sync::CancellationToken::new();
+        let (progress, mut events) = Progress::channel();
+        let progress = progress.with_cancellation(token.clone());
+
+        let cancel_once_started = async {
+            while let Some(event) = events.recv().await {
+                if matches!(event, ProgressEvent::FileProgress { .. }) {
+                    break;
+                }
+            }
+            token.cancel();
+        };
+
+        let (report, _) = tokio::join!(super::download_collection(&files, &progress), cancel_once_started);
+        assert!(report.cancelled);
+        assert!(matches!(report.into_result(), Err(Error::Cancelled)));
+        for name in ["cancelled.bin", "also-cancelled.bin"] {
+            let download = Download { path: temp_path(&dir, name), url: String::new() };
+            assert!(!std::path::Path::new(&download.path).exists());
+            assert!(!std::path::Path::new(&download.part_path()).exists());
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn decompresses_lzma_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut compressed = vec![];
+        lzma_rs::lzma_compress(&mut CONTENT.as_bytes(), &mut compressed).unwrap();
+        let compressed_sha1 = crate::resources::hash::sha1_hex(&compressed);
+        let compressed_size = compressed.len();
+        let (addr, server) = warp::serve(warp::any().map(move || compressed.clone())).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let download = DownloadType::Lzma(super::DownloadLzma {
+            download: Download { path: temp_path(&dir, "runtime-file"), url: format!("http://{addr}/java.lzma") },
+            compressed_size,
+            compressed_sha1,
+            size: CONTENT.len(),
+            sha1: crate::resources::hash::sha1_hex(CONTENT.as_bytes()),
+        });
+        let _ = std::fs::remove_file(&download.inner().path);
+
+        download.download().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&download.inner().path).unwrap(), CONTENT);
+        assert!(download.is_present(Verification::Hash));
+        assert!(download.download().await.unwrap().already_present);
+    }
+
+    #[test]
+    fn cleans_only_stale_parts() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("launcher [1]");
+        std::fs::create_dir_all(root.join("libraries")).unwrap();
+        let old = std::time::SystemTime::now() - Duration::from_secs(48 * 60 * 60);
+        for name in ["fresh.jar.part", "stale.jar.part", "resumable.jar.part", "resumable.jar.part.meta", "orphan.jar.part.meta"] {
+            let file = std::fs::File::create(root.join("libraries").join(name)).unwrap();
+            if name != "fresh.jar.part" {
+                file.set_modified(old).unwrap();
+            }
+        }
+
+        assert_eq!(super::clean_stale_parts(&root, Duration::from_secs(24 * 60 * 60)).unwrap(), 1);
+        let remaining = |name: &str| root.join("libraries").join(name).exists();
+        assert!(remaining("fresh.jar.part"));
+        assert!(!remaining("stale.jar.part"));
+        assert!(remaining("resumable.jar.part") && remaining("resumable.jar.part.meta"));
+        assert!(!remaining("orphan.jar.part.meta"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn restarts_on_mismatched_content_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, seen_ranges) = serve_shifted_ranges(true, 4);
+        let download = interrupted_download(&dir, "shifted.tar.gz", url);
+
+        download.download_with_retry(None, None, &no_retries(), &Progress::none()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), CONTENT);
+        assert_eq!(*seen_ranges.lock().unwrap(), [Some(String::from("bytes=8-")), None]);
     }
 }
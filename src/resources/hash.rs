@@ -0,0 +1,47 @@
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    hex::encode(Sha1::digest(data))
+}
+
+pub fn file_sha1(path: &str) -> Result<String> {
+    let mut hasher = Sha1::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+pub fn check_sha1(path: &str, data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha1_hex(data);
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(Error::Sha1Mismatch {
+            path: path.to_owned(),
+            expected: expected.to_owned(),
+            actual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_sha1, sha1_hex};
+
+    #[test]
+    fn hashes_known_value() {
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn rejects_mismatch() {
+        assert!(check_sha1("abc", b"abc", "A9993E364706816ABA3E25717850C26C9CD0D89D").is_ok());
+        assert!(check_sha1("abc", b"abd", "a9993e364706816aba3e25717850c26c9cd0d89d").is_err());
+    }
+}
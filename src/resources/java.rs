@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+use crate::{path_with_launcher, store};
+
+/// Where a registered Java installation came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JavaSource {
+    /// One of Mojang's `java-runtime` components.
+    Mojang,
+    /// A JDK downloaded through the foojay disco API.
+    Foojay,
+    /// Installed on the system, outside the launcher directory.
+    System,
+}
+
+impl fmt::Display for JavaSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JavaSource::Mojang => "mojang",
+            JavaSource::Foojay => "foojay",
+            JavaSource::System => "system",
+        })
+    }
+}
+
+impl FromStr for JavaSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "mojang" => Ok(JavaSource::Mojang),
+            "foojay" => Ok(JavaSource::Foojay),
+            "system" => Ok(JavaSource::System),
+            _ => Err(format!("unknown java source {s}")),
+        }
+    }
+}
+
+/// A Java runtime the launcher knows about, as reported by the runtime itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavaInstallation {
+    /// The `java` executable.
+    pub path: PathBuf,
+    pub vendor: String,
+    /// Full version, e.g. `17.0.8` or `1.8.0_382`.
+    pub version: String,
+    pub major_version: u32,
+    /// `os.arch` of the runtime, e.g. `amd64` or `aarch64`.
+    pub arch: String,
+    pub source: JavaSource,
+}
+
+impl JavaInstallation {
+    /// Runs `java -XshowSettings:properties -version` and reads the installation's details
+    /// from the properties it prints.
+    pub async fn probe(path: &Path, source: JavaSource) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidJava {
+            path: path.display().to_string(),
+            reason,
+        };
+        let output = tokio::process::Command::new(path)
+            .args(["-XshowSettings:properties", "-version"])
+            .output()
+            .await
+            .map_err(|e| invalid(e.to_string()))?;
+        if !output.status.success() {
+            return Err(invalid(format!("exited with {}", output.status)));
+        }
+        // the JVM prints its settings to stderr
+        let properties = parse_properties(&String::from_utf8_lossy(&output.stderr));
+        let property = |name: &str| {
+            properties
+                .get(name)
+                .cloned()
+                .ok_or_else(|| invalid(format!("{name} missing from -XshowSettings output")))
+        };
+
+        let version = property("java.version")?;
+        Ok(Self {
+            path: path.to_owned(),
+            major_version: major_version(&version).ok_or_else(|| invalid(format!("unrecognized version {version}")))?,
+            version,
+            vendor: property("java.vendor")?,
+            arch: property("os.arch")?,
+            source,
+        })
+    }
+
+    fn from_model(model: store::models::JavaInstallation) -> Option<Self> {
+        Some(Self {
+            path: PathBuf::from(model.path),
+            vendor: model.vendor,
+            version: model.version,
+            major_version: model.major_version.try_into().ok()?,
+            arch: model.arch,
+            source: model.source.parse().ok()?,
+        })
+    }
+
+    fn to_model(&self) -> store::models::JavaInstallation {
+        store::models::JavaInstallation {
+            id: None,
+            path: self.path.to_string_lossy().into_owned(),
+            vendor: self.vendor.clone(),
+            version: self.version.clone(),
+            major_version: self.major_version as i32,
+            arch: self.arch.clone(),
+            source: self.source.to_string(),
+        }
+    }
+}
+
+/// Parses the `key = value` lines of `-XshowSettings:properties`. Multi-valued properties
+/// continue on indented lines without a key; only their first value is kept.
+fn parse_properties(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once(" = "))
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
+/// Major version of a `java.version`: `1.8.0_382` is 8, `17.0.8` is 17, `21` is 21.
+fn major_version(version: &str) -> Option<u32> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Numeric parts of a release, comparable across the ways one version gets spelled:
+/// `1.8.0_382`, `8u382` and `8.0.382+5` are all `[8, 0, 382]`. Build numbers are ignored.
+pub(crate) fn release_numbers(version: &str) -> Vec<u32> {
+    let version = version.split('+').next().unwrap_or_default();
+    let version = version
+        .strip_prefix("1.")
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(version);
+    let numbers = |version: &str| -> Vec<u32> {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    match version.split_once('u') {
+        Some((major, update)) => [numbers(major), vec![0], numbers(update)].concat(),
+        None => numbers(version),
+    }
+}
+
+/// Every registered installation. Entries whose executable is gone are skipped.
+pub fn installations() -> Result<Vec<JavaInstallation>> {
+    Ok(store::get_java_installations()?
+        .into_iter()
+        .filter_map(JavaInstallation::from_model)
+        .filter(|installation| installation.path.exists())
+        .collect())
+}
+
+/// Which installation wins when both a launcher-managed and a system runtime of the
+/// required major version are registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JavaPolicy {
+    /// Use runtimes the launcher installed, falling back to system ones.
+    #[default]
+    PreferManaged,
+    /// Use system runtimes, falling back to ones the launcher installed.
+    PreferSystem,
+}
+
+lazy_static! {
+    static ref JAVA_POLICY: RwLock<JavaPolicy> = RwLock::new(JavaPolicy::default());
+}
+
+pub fn set_java_policy(policy: JavaPolicy) {
+    *JAVA_POLICY.write().unwrap() = policy;
+}
+
+pub fn java_policy() -> JavaPolicy {
+    *JAVA_POLICY.read().unwrap()
+}
+
+/// A registered installation of exactly `major_version`, chosen according to `java_policy()`.
+pub fn find(major_version: u32) -> Result<Option<JavaInstallation>> {
+    Ok(choose(installations()?, major_version, java_policy()))
+}
+
+fn choose(installations: Vec<JavaInstallation>, major_version: u32, policy: JavaPolicy) -> Option<JavaInstallation> {
+    let (system, managed): (Vec<_>, Vec<_>) = installations
+        .into_iter()
+        .filter(|installation| installation.major_version == major_version)
+        .partition(|installation| installation.source == JavaSource::System);
+    match policy {
+        JavaPolicy::PreferManaged => managed.into_iter().chain(system).next(),
+        JavaPolicy::PreferSystem => system.into_iter().chain(managed).next(),
+    }
+}
+
+/// Probes the `java` executable at `path` and records it in the registry.
+pub async fn register(path: &Path, source: JavaSource) -> Result<JavaInstallation> {
+    let installation = JavaInstallation::probe(path, source).await?;
+    store::store_java_installation(&installation.to_model())?;
+    info!(
+        "registered {} java {} ({}) at {}",
+        installation.vendor, installation.version, installation.arch, path.display()
+    );
+    Ok(installation)
+}
+
+pub fn unregister(path: &Path) -> Result<()> {
+    store::remove_java_installation(&path.to_string_lossy())
+}
+
+/// Drops registry entries whose executable no longer exists, returning their paths.
+pub fn prune() -> Result<Vec<PathBuf>> {
+    let mut pruned = vec![];
+    for model in store::get_java_installations()? {
+        let path = PathBuf::from(&model.path);
+        if !path.exists() {
+            unregister(&path)?;
+            pruned.push(path);
+        }
+    }
+    if !pruned.is_empty() {
+        info!("removed {} missing java installations from the registry", pruned.len());
+    }
+    Ok(pruned)
+}
+
+/// `java` executables that may be system installs: `JAVA_HOME`, every `PATH` entry,
+/// `/usr/lib/jvm`, SDKMAN candidates and macOS' `JavaVirtualMachines`. Symlinks such as
+/// `/usr/bin/java` are resolved so each runtime appears once. Runtimes under `launcher`, the
+/// launcher directory, are managed by the launcher and skipped even when `PATH` leads to them.
+fn system_candidates(java_home: Option<PathBuf>, path: Option<OsString>, home: Option<PathBuf>, launcher: &Path) -> Vec<PathBuf> {
+    let executable = if cfg!(target_os = "windows") { "java.exe" } else { "java" };
+    let mut candidates = vec![];
+    if let Some(java_home) = java_home {
+        candidates.push(java_home.join("bin").join(executable));
+    }
+    if let Some(path) = path {
+        candidates.extend(std::env::split_paths(&path).map(|directory| directory.join(executable)));
+    }
+    let mut roots = vec![PathBuf::from("/usr/lib/jvm"), PathBuf::from("/Library/Java/JavaVirtualMachines")];
+    if let Some(home) = home {
+        roots.push(home.join(".sdkman/candidates/java"));
+    }
+    for root in roots {
+        for pattern in ["*/bin", "*/Contents/Home/bin"] {
+            let pattern = root.join(pattern).join(executable);
+            candidates.extend(glob::glob(&pattern.to_string_lossy()).into_iter().flatten().flatten());
+        }
+    }
+
+    let launcher = std::fs::canonicalize(launcher).unwrap_or_else(|_| launcher.to_owned());
+    let mut seen = vec![];
+    for candidate in candidates {
+        if let Ok(java) = std::fs::canonicalize(&candidate) {
+            if java.is_file() && !java.starts_with(&launcher) && !seen.contains(&java) {
+                seen.push(java);
+            }
+        }
+    }
+    seen
+}
+
+/// Finds Java runtimes installed on the system and probes their real version and architecture.
+/// Executables that fail to run are logged and skipped.
+pub async fn discover() -> Vec<JavaInstallation> {
+    let candidates = system_candidates(
+        std::env::var_os("JAVA_HOME").map(PathBuf::from),
+        std::env::var_os("PATH"),
+        dirs::home_dir(),
+        Path::new(&path_with_launcher("")),
+    );
+    let mut found = vec![];
+    for java in candidates {
+        match JavaInstallation::probe(&java, JavaSource::System).await {
+            Ok(installation) => found.push(installation),
+            Err(e) => warn!("ignoring {}: {}", java.display(), e),
+        }
+    }
+    found
+}
+
+/// Discovers system runtimes and records them in the registry, so `find` can offer them.
+pub async fn register_system() -> Result<Vec<JavaInstallation>> {
+    let found = discover().await;
+    for installation in &found {
+        store::store_java_installation(&installation.to_model())?;
+    }
+    info!("found {} system java installations", found.len());
+    Ok(found)
+}
+
+/// Runs `register_system` the first time it's needed in this process.
+pub(crate) async fn register_system_once() {
+    static SCANNED: OnceCell<()> = OnceCell::const_new();
+    SCANNED
+        .get_or_init(|| async {
+            if let Err(e) = register_system().await {
+                warn!("could not register system java installations: {}", e);
+            }
+        })
+        .await;
+}
+
+/// Directories under `jdk` with no registered runtime inside them.
+fn unregistered_roots(jdk: &Path, registered: &[JavaInstallation]) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(jdk) else {
+        return vec![];
+    };
+    let mut roots: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        // the downloaded archives live next to the directories they were extracted into
+        .filter(|root| root.is_dir())
+        .filter(|root| !registered.iter().any(|installation| installation.path.starts_with(root)))
+        .collect();
+    roots.sort();
+    roots
+}
+
+/// Registers the JDKs under `jdk/` that aren't in the registry yet, such as ones extracted
+/// before it existed, the first time it's needed in this process.
+pub(crate) async fn register_foojay_once() {
+    static SCANNED: OnceCell<()> = OnceCell::const_new();
+    SCANNED
+        .get_or_init(|| async {
+            let registered = match installations() {
+                Ok(registered) => registered,
+                Err(e) => {
+                    warn!("could not read the java registry: {}", e);
+                    return;
+                }
+            };
+            for root in unregistered_roots(Path::new(&path_with_launcher("jdk")), &registered) {
+                if let Err(e) = register_root(&root, JavaSource::Foojay).await {
+                    warn!("ignoring {}: {}", root.display(), e);
+                }
+            }
+        })
+        .await;
+}
+
+/// Registers the runtime rooted at `root`, such as the top-level directory of an extracted JDK archive.
+pub(crate) async fn register_root(root: &Path, source: JavaSource) -> Result<JavaInstallation> {
+    let java = ["bin/java", "bin/java.exe", "Contents/Home/bin/java"]
+        .iter()
+        .map(|executable| root.join(executable))
+        .find(|java| java.is_file())
+        .ok_or_else(|| Error::InvalidJava {
+            path: root.display().to_string(),
+            reason: String::from("no java executable found"),
+        })?;
+    register(&java, source).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        choose, major_version, parse_properties, release_numbers, system_candidates, unregistered_roots, JavaInstallation,
+        JavaPolicy, JavaSource,
+    };
+
+    const SETTINGS: &str = "Property settings:
+    file.encoding = UTF-8
+    java.library.path = /usr/java/packages/lib
+        /usr/lib64
+        /lib64
+    java.vendor = Eclipse Adoptium
+    java.version = 17.0.8
+    os.arch = amd64
+
+openjdk version \"17.0.8\" 2023-07-18
+";
+
+    #[test]
+    fn parses_show_settings_output() {
+        let properties = parse_properties(SETTINGS);
+        assert_eq!(properties["java.vendor"], "Eclipse Adoptium");
+        assert_eq!(properties["java.version"], "17.0.8");
+        assert_eq!(properties["os.arch"], "amd64");
+        assert_eq!(properties["java.library.path"], "/usr/java/packages/lib");
+    }
+
+    #[test]
+    fn reads_major_versions() {
+        assert_eq!(major_version("1.8.0_382"), Some(8));
+        assert_eq!(major_version("17.0.8"), Some(17));
+        assert_eq!(major_version("21"), Some(21));
+        assert_eq!(major_version("22-ea"), Some(22));
+        assert_eq!(major_version("openjdk"), None);
+    }
+
+    #[test]
+    fn compares_release_spellings() {
+        assert_eq!(release_numbers("1.8.0_382"), vec![8, 0, 382]);
+        assert_eq!(release_numbers("8u382"), vec![8, 0, 382]);
+        assert_eq!(release_numbers("8.0.382+5"), vec![8, 0, 382]);
+        assert_eq!(release_numbers("17.0.8.1+1"), vec![17, 0, 8, 1]);
+        assert!(release_numbers("17.0.10") > release_numbers("17.0.8.1"));
+        assert!(release_numbers("11.0.2") > release_numbers("8u382"));
+    }
+
+    fn installation(path: &str, major_version: u32, source: JavaSource) -> JavaInstallation {
+        JavaInstallation {
+            path: PathBuf::from(path),
+            vendor: String::from("Eclipse Adoptium"),
+            version: format!("{major_version}.0.1"),
+            major_version,
+            arch: String::from("amd64"),
+            source,
+        }
+    }
+
+    #[test]
+    fn chooses_by_policy() {
+        let installations = vec![
+            installation("/usr/lib/jvm/java-17/bin/java", 17, JavaSource::System),
+            installation("/launcher/jdk/jdk-17/bin/java", 17, JavaSource::Foojay),
+            installation("/launcher/jdk/jdk-8/bin/java", 8, JavaSource::Foojay),
+        ];
+
+        let managed = choose(installations.clone(), 17, JavaPolicy::PreferManaged).unwrap();
+        assert_eq!(managed.source, JavaSource::Foojay);
+        let system = choose(installations.clone(), 17, JavaPolicy::PreferSystem).unwrap();
+        assert_eq!(system.source, JavaSource::System);
+        assert_eq!(choose(installations.clone(), 8, JavaPolicy::PreferSystem).unwrap().major_version, 8);
+        assert!(choose(installations, 1, JavaPolicy::PreferManaged).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_system_candidates() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().to_owned();
+        let sdkman_java = root.join(".sdkman/candidates/java/17.0.8-tem/bin");
+        let java_home = root.join("jdk-21");
+        for bin in [&sdkman_java, &java_home.join("bin")] {
+            std::fs::create_dir_all(bin).unwrap();
+            std::fs::write(bin.join("java"), "").unwrap();
+        }
+        let path_bin = root.join("bin");
+        std::fs::create_dir_all(&path_bin).unwrap();
+        std::os::unix::fs::symlink(sdkman_java.join("java"), path_bin.join("java")).unwrap();
+
+        let launcher = root.join("launcher");
+        let managed_bin = launcher.join("jdk/jdk-17.0.8+7-jre/bin");
+        std::fs::create_dir_all(&managed_bin).unwrap();
+        std::fs::write(managed_bin.join("java"), "").unwrap();
+
+        let path = std::env::join_paths([path_bin, root.join("missing"), managed_bin.clone()]).unwrap();
+        let candidates = system_candidates(Some(java_home.clone()), Some(path), Some(root.clone()), &launcher);
+        let canonical = |path: &Path| std::fs::canonicalize(path).unwrap();
+        assert!(candidates.contains(&canonical(&java_home.join("bin/java"))));
+        assert_eq!(candidates.iter().filter(|java| **java == canonical(&sdkman_java.join("java"))).count(), 1);
+        assert!(!candidates.contains(&canonical(&managed_bin.join("java"))));
+    }
+
+    #[test]
+    fn finds_unregistered_roots() {
+        let temp = tempfile::tempdir().unwrap();
+        let jdk = temp.path().to_owned();
+        for root in ["jdk-17.0.8+7-jre", "jdk-21.0.1+12"] {
+            std::fs::create_dir_all(jdk.join(root).join("bin")).unwrap();
+        }
+        std::fs::write(jdk.join("OpenJDK17U-jre_x64_linux_hotspot_17.0.8_7.tar.gz"), "").unwrap();
+        let registered = [installation(&jdk.join("jdk-17.0.8+7-jre/bin/java").to_string_lossy(), 17, JavaSource::Foojay)];
+
+        assert_eq!(unregistered_roots(&jdk, &registered), vec![jdk.join("jdk-21.0.1+12")]);
+        assert!(unregistered_roots(&jdk.join("missing"), &registered).is_empty());
+    }
+}
@@ -1,37 +1,150 @@
 use serde::{Serialize, Deserialize};
+use tracing::warn;
 
 
-use crate::{error::{Result, Error}, client, path_with_launcher};
+use crate::{error::{Result, Error}, http::client, path_with_launcher};
 
+use super::java::release_numbers;
 use super::download::{Downloadeable, DownloadType, DownloadArchive, Download};
 
 #[derive(Debug, Clone)]
 pub struct JavaVersion {
     major_version: u32,
+    /// Full version, e.g. `17.0.8.1+1`.
+    java_version: String,
+    distribution: String,
     download_url: String,
-    filename: String
+    filename: String,
+    size: Option<u64>,
+    sha256: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct DiscoApiResult {
-    result: Vec<DiscoApiPackage>,
+struct DiscoApiResult<T> {
+    result: Vec<T>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct DiscoApiPackage {
     major_version: i32,
+    java_version: String,
+    distribution: String,
     filename: String,
+    size: Option<u64>,
+    package_type: String,
+    lib_c_type: Option<String>,
     links: Links
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Links {
-    pkg_download_redirect: String
+    pkg_download_redirect: String,
+    pkg_info_uri: String,
 }
 
-impl JavaVersion {
-    pub async fn search(major_version: i32) -> Result<Self> {
-        let versions = client.get("https://api.foojay.io/disco/v3.0/packages")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DiscoApiPackageInfo {
+    checksum: String,
+    checksum_type: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageType {
+    Jre,
+    Jdk,
+}
+
+impl PackageType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PackageType::Jre => "jre",
+            PackageType::Jdk => "jdk",
+        }
+    }
+}
+
+/// The C library a Linux JDK build is linked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibC {
+    Glibc,
+    Musl,
+}
+
+impl LibC {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LibC::Glibc => "glibc",
+            LibC::Musl => "musl",
+        }
+    }
+
+    /// The C library of this system: musl when its dynamic loader is present (Alpine and
+    /// friends), glibc otherwise. `None` outside Linux, where builds don't differ by libc.
+    pub fn detect() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let musl = glob::glob("/lib/ld-musl-*").into_iter().flatten().next().is_some();
+        Some(if musl { LibC::Musl } else { LibC::Glibc })
+    }
+}
+
+/// A search of the foojay disco API for a JDK build.
+#[derive(Debug, Clone)]
+pub struct JdkQuery {
+    major_version: u32,
+    distributions: Vec<String>,
+    package_type: PackageType,
+    lib_c: Option<LibC>,
+}
+
+impl JdkQuery {
+    /// Temurin or Microsoft JRE of `major_version`, for this system's libc.
+    pub fn new(major_version: u32) -> Self {
+        Self {
+            major_version,
+            distributions: vec![String::from("temurin"), String::from("microsoft")],
+            package_type: PackageType::Jre,
+            lib_c: LibC::detect(),
+        }
+    }
+
+    /// Acceptable distributions, most preferred first, e.g. `["zulu", "temurin"]`.
+    pub fn distributions(mut self, distributions: &[&str]) -> Self {
+        self.distributions = distributions.iter().map(|distribution| distribution.to_string()).collect();
+        self
+    }
+
+    pub fn package_type(mut self, package_type: PackageType) -> Self {
+        self.package_type = package_type;
+        self
+    }
+
+    pub fn lib_c(mut self, lib_c: LibC) -> Self {
+        self.lib_c = Some(lib_c);
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("jdk_version", self.major_version.to_string()),
+            ("distribution", self.distributions.join(",")),
+            ("package_type", self.package_type.as_str().to_owned()),
+            ("latest", String::from("available")),
+            ("javafx_bundled", String::from("false")),
+            ("directly_downloadable", String::from("true")),
+            ("free_use_in_production", String::from("true")),
+        ];
+        if let Some(lib_c) = self.lib_c {
+            params.push(("lib_c_type", lib_c.as_str().to_owned()));
+        }
+        params
+    }
+
+    /// The latest patch of the most preferred distribution that has a matching build,
+    /// along with its published checksum.
+    pub async fn search(&self) -> Result<JavaVersion> {
+        let packages = client().get("https://api.foojay.io/disco/v3.0/packages")
             .query(&[
                 #[cfg(target_arch="x86")]
                 ("arch", "x86"),
@@ -57,37 +170,84 @@ impl JavaVersion {
                 ("archive_type", "zip"),
                 #[cfg(target_os = "linux")]
                 ("archive_type", "tar.gz"),
-                ("package_type", "jre"),
-                ("javafx_bundled", "false"),
-                ("directly_downloadeable", "true"),
-                ("free_use_in_production", "true"),
-                ("distribution", "temurin,microsoft")
             ])
+            .query(&self.params())
             .send().await?
-            .json::<DiscoApiResult>().await?
+            .error_for_status()?
+            .json::<DiscoApiResult<DiscoApiPackage>>().await?
             .result;
-        
-        let version = 
-            versions
+
+        let package = self.select(packages).ok_or(Error::JavaVersionNotFoundError)?;
+        let sha256 = package_sha256(&package.links.pkg_info_uri).await?;
+        Ok(JavaVersion {
+            major_version: package.major_version as u32,
+            java_version: package.java_version,
+            distribution: package.distribution,
+            filename: package.filename,
+            size: package.size,
+            download_url: package.links.pkg_download_redirect,
+            sha256,
+        })
+    }
+
+    fn select(&self, packages: Vec<DiscoApiPackage>) -> Option<DiscoApiPackage> {
+        packages
             .into_iter()
-            .filter(|version| 
-                version.major_version == major_version
-                //even tough operating_system is set to "linux", the api returns "alpine-linux" versions, which are not compatible
-                && !version.filename.contains("alpine-linux")
-            )
-            .next();
-            
-        return if let Some(version) = version {
-            Ok(
-                Self {
-                    major_version: version.major_version as u32,
-                    filename: version.filename,
-                    download_url: version.links.pkg_download_redirect
-                }
-            )
-        } else {
-            Err(Error::JavaVersionNotFoundError)
-        } 
+            .filter(|package| {
+                package.major_version == self.major_version as i32
+                    && package.package_type == self.package_type.as_str()
+                    && self.lib_c.is_none_or(|lib_c| package.lib_c_type.as_deref() == Some(lib_c.as_str()))
+            })
+            .filter_map(|package| {
+                let preference = self.distributions.iter().position(|distribution| *distribution == package.distribution)?;
+                Some((preference, package))
+            })
+            .min_by(|(a_preference, a), (b_preference, b)| {
+                a_preference
+                    .cmp(b_preference)
+                    .then_with(|| release_numbers(&b.java_version).cmp(&release_numbers(&a.java_version)))
+            })
+            .map(|(_, package)| package)
+    }
+}
+
+/// The package's SHA-256 from its disco info, if the distribution publishes one.
+async fn package_sha256(info_url: &str) -> Result<Option<String>> {
+    let info = client().get(info_url)
+        .send().await?
+        .error_for_status()?
+        .json::<DiscoApiResult<DiscoApiPackageInfo>>().await?
+        .result
+        .into_iter()
+        .next();
+    Ok(match info {
+        Some(info) if info.checksum_type.eq_ignore_ascii_case("sha256") && !info.checksum.is_empty() => Some(info.checksum),
+        Some(info) => {
+            warn!("no sha256 published for {} ({}), skipping verification", info_url, info.checksum_type);
+            None
+        }
+        None => None,
+    })
+}
+
+impl JavaVersion {
+    pub async fn search(major_version: i32) -> Result<Self> {
+        let major_version = major_version.try_into().map_err(|_| Error::JavaVersionNotFoundError)?;
+        JdkQuery::new(major_version).search().await
+    }
+
+    /// Full version, e.g. `17.0.8.1+1`.
+    pub fn java_version(&self) -> &str {
+        &self.java_version
+    }
+
+    pub fn distribution(&self) -> &str {
+        &self.distribution
+    }
+
+    /// Archive size in bytes, as reported by the disco API.
+    pub fn size(&self) -> Option<u64> {
+        self.size
     }
 }
 
@@ -99,7 +259,8 @@ impl Downloadeable for JavaVersion {
                     path: path_with_launcher("jdk/") + &self.filename,
                     url: self.download_url.clone()
                 },
-                destination: path_with_launcher("jdk")
+                destination: path_with_launcher("jdk"),
+                sha256: self.sha256.clone(),
             }
         )
     }
@@ -110,7 +271,7 @@ mod tests {
     use tracing_test::traced_test;
     use crate::resources::download::Downloadeable;
 
-    use super::JavaVersion;
+    use super::{DiscoApiPackage, JavaVersion, JdkQuery, LibC, Links, PackageType};
     use tracing::info;
 
     #[tokio::test]
@@ -120,4 +281,35 @@ mod tests {
         info!("{:#?}", version);
         version.download_info().download().await.unwrap();
     }
-}
\ No newline at end of file
+
+    fn package(distribution: &str, java_version: &str, lib_c: &str) -> DiscoApiPackage {
+        DiscoApiPackage {
+            major_version: 17,
+            java_version: java_version.to_owned(),
+            distribution: distribution.to_owned(),
+            filename: format!("{distribution}-{java_version}-{lib_c}.tar.gz"),
+            size: None,
+            package_type: String::from("jre"),
+            lib_c_type: Some(lib_c.to_owned()),
+            links: Links { pkg_download_redirect: String::new(), pkg_info_uri: String::new() },
+        }
+    }
+
+    #[test]
+    fn selects_latest_patch_of_preferred_distribution() {
+        let packages = vec![
+            package("microsoft", "17.0.9+8", "glibc"),
+            package("temurin", "17.0.8+7", "glibc"),
+            package("temurin", "17.0.10+7", "glibc"),
+            package("temurin", "17.0.11+9", "musl"),
+        ];
+        let query = JdkQuery::new(17).lib_c(LibC::Glibc);
+
+        assert_eq!(query.select(packages.clone()).unwrap().java_version, "17.0.10+7");
+        let musl = query.clone().lib_c(LibC::Musl).select(packages.clone()).unwrap();
+        assert_eq!(musl.java_version, "17.0.11+9");
+        let microsoft = query.clone().distributions(&["microsoft", "temurin"]).select(packages.clone()).unwrap();
+        assert_eq!(microsoft.distribution, "microsoft");
+        assert!(query.package_type(PackageType::Jdk).select(packages).is_none());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use super::download::DownloadConfig;
+
+/// Shared connection and bandwidth limits, built from a `DownloadConfig`.
+pub struct Limiter {
+    global: Arc<Semaphore>,
+    per_host: Option<usize>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+    bandwidth: Option<TokenBucket>,
+}
+
+/// Held while a request is in flight; dropping it frees the connection slots.
+pub struct ConnectionPermit {
+    _global: OwnedSemaphorePermit,
+    _host: Option<OwnedSemaphorePermit>,
+}
+
+impl Limiter {
+    pub fn new(config: &DownloadConfig) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(config.concurrency.max(1))),
+            per_host: config.max_connections_per_host.map(|limit| limit.max(1)),
+            hosts: Mutex::new(HashMap::new()),
+            bandwidth: config.bandwidth_limit.map(TokenBucket::new),
+        }
+    }
+
+    pub async fn acquire(&self, url: &str) -> ConnectionPermit {
+        let host = match self.per_host {
+            Some(limit) => {
+                let host = reqwest::Url::parse(url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_owned))
+                    .unwrap_or_default();
+                let semaphore = self
+                    .hosts
+                    .lock()
+                    .unwrap()
+                    .entry(host)
+                    .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                    .clone();
+                Some(semaphore.acquire_owned().await.unwrap())
+            }
+            None => None,
+        };
+        ConnectionPermit {
+            _global: self.global.clone().acquire_owned().await.unwrap(),
+            _host: host,
+        }
+    }
+
+    /// Waits until `bytes` fit in the bandwidth cap, if there is one.
+    pub async fn throttle(&self, bytes: usize) {
+        if let Some(bucket) = &self.bandwidth {
+            bucket.consume(bytes).await;
+        }
+    }
+}
+
+/// Token bucket holding up to one second worth of bytes at `rate` bytes per second.
+/// Chunks bigger than what is available put the bucket in debt, which later callers wait out.
+struct TokenBucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate.max(1) as f64;
+        Self {
+            rate,
+            state: Mutex::new((rate, Instant::now())),
+        }
+    }
+
+    async fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let (tokens, last) = *state;
+            let tokens = (tokens + (now - last).as_secs_f64() * self.rate).min(self.rate) - bytes as f64;
+            *state = (tokens, now);
+            if tokens < 0.0 {
+                Duration::from_secs_f64(-tokens / self.rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{Limiter, TokenBucket};
+    use crate::resources::download::DownloadConfig;
+
+    #[tokio::test]
+    async fn limits_bandwidth() {
+        let bucket = TokenBucket::new(10_000);
+        let start = Instant::now();
+        bucket.consume(10_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        bucket.consume(5_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn limits_connections_per_host() {
+        let limiter = Limiter::new(&DownloadConfig {
+            concurrency: 4,
+            max_connections_per_host: Some(1),
+            ..Default::default()
+        });
+
+        let _first = limiter.acquire("https://libraries.minecraft.net/a.jar").await;
+        let _other_host = limiter.acquire("https://resources.download.minecraft.net/ab/cd").await;
+        let second = tokio::time::timeout(
+            Duration::from_millis(50),
+            limiter.acquire("https://libraries.minecraft.net/b.jar"),
+        )
+        .await;
+        assert!(second.is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use crate::{error::{Error, Result}, path_with_launcher};
+
+use super::download::Downloadeable;
+use super::java::{self, release_numbers, JavaInstallation, JavaSource};
+use super::jdk::{self, JdkQuery, PackageType};
+use super::progress::Progress;
+use super::runtime::JavaRuntime;
+
+/// Java runtimes older than 1.17 don't name one; the vanilla launcher runs them on Java 8.
+const DEFAULT_MAJOR_VERSION: u32 = 8;
+
+/// A Java runtime the launcher installed itself, under `runtime/` (Mojang) or `jdk/` (foojay).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedRuntime {
+    pub installation: JavaInstallation,
+    /// The directory the runtime was installed into, deleted when it's uninstalled.
+    pub root: PathBuf,
+}
+
+/// A newer patch release of an installed runtime's major version.
+#[derive(Debug, Clone)]
+pub struct RuntimeUpdate {
+    pub current: ManagedRuntime,
+    /// The new release, e.g. `17.0.10`.
+    pub version: String,
+    release: Release,
+}
+
+#[derive(Debug, Clone)]
+enum Release {
+    Mojang(JavaRuntime),
+    Foojay(jdk::JavaVersion),
+}
+
+/// Where a managed runtime lives: `runtime/<component>/<platform>` for Mojang's and
+/// `jdk/<directory>` for foojay's. `None` for anything outside those directories.
+fn managed_root(java: &Path, source: JavaSource, launcher: &Path) -> Option<PathBuf> {
+    let (base, depth) = match source {
+        JavaSource::Mojang => (launcher.join("runtime"), 2),
+        JavaSource::Foojay => (launcher.join("jdk"), 1),
+        JavaSource::System => return None,
+    };
+    let relative = java.strip_prefix(&base).ok()?;
+    let root: PathBuf = relative.components().take(depth).collect();
+    // the executable itself must be below the root
+    (relative.components().count() > depth && root.components().count() == depth).then(|| base.join(root))
+}
+
+/// The foojay distribution published by `vendor`, a runtime's `java.vendor`, if it's known.
+fn foojay_distribution(vendor: &str) -> Option<&'static str> {
+    match vendor {
+        "Eclipse Adoptium" => Some("temurin"),
+        "Microsoft" => Some("microsoft"),
+        "Azul Systems, Inc." => Some("zulu"),
+        "Amazon.com Inc." => Some("corretto"),
+        "BellSoft" => Some("liberica"),
+        "SAP SE" => Some("sap_machine"),
+        _ => None,
+    }
+}
+
+/// Whether the runtime at `root` is a full JDK, which ships `javac`, or a JRE.
+fn package_type(root: &Path) -> PackageType {
+    if ["bin/javac", "bin/javac.exe", "Contents/Home/bin/javac"].iter().any(|javac| root.join(javac).is_file()) {
+        PackageType::Jdk
+    } else {
+        PackageType::Jre
+    }
+}
+
+/// Every registered runtime the launcher installed itself.
+pub fn managed_runtimes() -> Result<Vec<ManagedRuntime>> {
+    let launcher = PathBuf::from(path_with_launcher(""));
+    Ok(java::installations()?
+        .into_iter()
+        .filter_map(|installation| {
+            let root = managed_root(&installation.path, installation.source, &launcher)?;
+            Some(ManagedRuntime { installation, root })
+        })
+        .collect())
+}
+
+impl ManagedRuntime {
+    /// The Mojang component, e.g. `java-runtime-gamma`, for runtimes installed from Mojang.
+    pub fn component(&self) -> Option<String> {
+        match self.installation.source {
+            JavaSource::Mojang => Some(self.root.parent()?.file_name()?.to_string_lossy().into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Asks the runtime's source for the latest release of its major version, from the same
+    /// vendor and as the same JRE or JDK package. `Ok(None)` means the installed one is current,
+    /// or that its vendor isn't one foojay can be asked about.
+    pub async fn check_update(&self) -> Result<Option<RuntimeUpdate>> {
+        let (version, release) = match self.component() {
+            Some(component) => match JavaRuntime::find(&component).await? {
+                Some(runtime) => (runtime.version.clone(), Release::Mojang(runtime)),
+                None => return Ok(None),
+            },
+            None => {
+                let Some(distribution) = foojay_distribution(&self.installation.vendor) else {
+                    warn!("no foojay distribution known for {}, not checking for updates", self.installation.vendor);
+                    return Ok(None);
+                };
+                let jdk = JdkQuery::new(self.installation.major_version)
+                    .distributions(&[distribution])
+                    .package_type(package_type(&self.root))
+                    .search()
+                    .await?;
+                (jdk.java_version().to_owned(), Release::Foojay(jdk))
+            }
+        };
+
+        if release_numbers(&version) > release_numbers(&self.installation.version) {
+            Ok(Some(RuntimeUpdate { current: self.clone(), version, release }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Deletes the runtime's files and removes it from the registry.
+    pub async fn uninstall(&self) -> Result<()> {
+        if tokio::fs::metadata(&self.root).await.is_ok() {
+            tokio::fs::remove_dir_all(&self.root).await?;
+        }
+        if let Some(component) = self.component() {
+            // the cached manifest, so a reinstall fetches a fresh one
+            let _ = tokio::fs::remove_file(self.root.with_extension("json")).await;
+            info!("uninstalled {} {}", component, self.installation.version);
+        } else {
+            info!("uninstalled {} {}", self.installation.vendor, self.installation.version);
+        }
+        java::unregister(&self.installation.path)
+    }
+}
+
+impl RuntimeUpdate {
+    /// Replaces the current runtime with the new release. Mojang runtimes are updated file by
+    /// file in their directory; a foojay JDK is extracted next to the old one, which is only
+    /// removed once the new one has been registered.
+    pub async fn upgrade(&self, progress: &Progress) -> Result<JavaInstallation> {
+        progress.check_cancelled()?;
+        let installation = match &self.release {
+            Release::Mojang(runtime) => {
+                runtime.install(progress).await?.into_result()?;
+                JavaInstallation::probe(&self.current.installation.path, JavaSource::Mojang).await?
+            }
+            Release::Foojay(jdk) => {
+                let status = jdk.download_info().download_with(progress).await?;
+                let root = status.extracted_to.ok_or(Error::JavaVersionNotFoundError)?;
+                let installation = java::register_root(&root, JavaSource::Foojay).await?;
+                if installation.major_version != self.current.installation.major_version
+                    || installation.vendor != self.current.installation.vendor
+                {
+                    java::unregister(&installation.path)?;
+                    if root != self.current.root {
+                        tokio::fs::remove_dir_all(&root).await?;
+                    }
+                    return Err(Error::JavaVersionNotFoundError);
+                }
+                if root != self.current.root {
+                    self.current.uninstall().await?;
+                }
+                installation
+            }
+        };
+        info!("upgraded java {} to {}", self.current.installation.version, installation.version);
+        Ok(installation)
+    }
+}
+
+/// Major versions asked for by the versions whose details are stored under `versions/`.
+/// Fails if any of them can't be read, as the set would be incomplete.
+fn stored_major_versions(versions: &Path) -> Result<BTreeSet<u32>> {
+    let pattern = versions.join("*").join("*.json");
+    let mut majors = BTreeSet::new();
+    for path in glob::glob(&pattern.to_string_lossy())? {
+        let path = path.map_err(std::io::Error::from)?;
+        let details = std::fs::read_to_string(&path)?;
+        let details: serde_json::Value = serde_json::from_str(&details).map_err(|e| {
+            warn!("could not parse {}: {}", path.display(), e);
+            e
+        })?;
+        majors.insert(
+            details["javaVersion"]["majorVersion"]
+                .as_u64()
+                .and_then(|major| major.try_into().ok())
+                .unwrap_or(DEFAULT_MAJOR_VERSION),
+        );
+    }
+    Ok(majors)
+}
+
+/// Uninstalls every managed runtime whose major version no stored version needs, and drops
+/// registry entries whose executable is gone. Returns the runtimes that were removed.
+///
+/// With no stored versions this would remove every managed runtime, so it's refused unless
+/// `force` is set.
+pub async fn collect_garbage(force: bool) -> Result<Vec<ManagedRuntime>> {
+    java::prune()?;
+    let needed = stored_major_versions(Path::new(&path_with_launcher("versions")))?;
+    if needed.is_empty() && !force {
+        return Err(Error::NoStoredVersions);
+    }
+    let unneeded: Vec<_> = managed_runtimes()?
+        .into_iter()
+        .filter(|runtime| !needed.contains(&runtime.installation.major_version))
+        .collect();
+    for runtime in &unneeded {
+        runtime.uninstall().await?;
+    }
+    Ok(unneeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::path::{Path, PathBuf};
+
+    use super::{foojay_distribution, managed_root, package_type, stored_major_versions};
+    use crate::resources::jdk::PackageType;
+    use crate::resources::java::JavaSource;
+
+    #[test]
+    fn finds_managed_roots() {
+        let launcher = Path::new("/data/liblauncher");
+        assert_eq!(
+            managed_root(&launcher.join("runtime/java-runtime-gamma/linux/bin/java"), JavaSource::Mojang, launcher),
+            Some(PathBuf::from("/data/liblauncher/runtime/java-runtime-gamma/linux"))
+        );
+        assert_eq!(
+            managed_root(&launcher.join("jdk/jdk-17.0.8+7-jre/bin/java"), JavaSource::Foojay, launcher),
+            Some(PathBuf::from("/data/liblauncher/jdk/jdk-17.0.8+7-jre"))
+        );
+        assert_eq!(managed_root(&launcher.join("jdk/java"), JavaSource::Foojay, launcher), None);
+        assert_eq!(managed_root(Path::new("/usr/lib/jvm/java-17/bin/java"), JavaSource::Foojay, launcher), None);
+        assert_eq!(managed_root(&launcher.join("jdk/jdk-17/bin/java"), JavaSource::System, launcher), None);
+    }
+
+    #[test]
+    fn keeps_vendor_and_package_type() {
+        assert_eq!(foojay_distribution("Eclipse Adoptium"), Some("temurin"));
+        assert_eq!(foojay_distribution("Azul Systems, Inc."), Some("zulu"));
+        assert_eq!(foojay_distribution("Homebrew"), None);
+
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().to_owned();
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        std::fs::write(root.join("bin/java"), "").unwrap();
+        assert_eq!(package_type(&root), PackageType::Jre);
+        std::fs::write(root.join("bin/javac"), "").unwrap();
+        assert_eq!(package_type(&root), PackageType::Jdk);
+    }
+
+    #[test]
+    fn reads_stored_major_versions() {
+        let temp = tempfile::tempdir().unwrap();
+        let versions = temp.path().to_owned();
+        for (id, details) in [
+            ("1.20.1", r#"{"javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17}}"#),
+            ("1.12.2", r#"{"javaVersion": {"component": "jre-legacy", "majorVersion": 8}}"#),
+            ("1.6.4", r#"{"id": "1.6.4"}"#),
+        ] {
+            std::fs::create_dir_all(versions.join(id)).unwrap();
+            std::fs::write(versions.join(id).join(format!("{id}.json")), details).unwrap();
+        }
+        std::fs::write(versions.join("version_manifest_v2.json"), r#"{"javaVersion": {"majorVersion": 21}}"#).unwrap();
+
+        assert_eq!(stored_major_versions(&versions).unwrap(), BTreeSet::from([8, 17]));
+
+        std::fs::create_dir_all(versions.join("broken")).unwrap();
+        std::fs::write(versions.join("broken").join("broken.json"), "{").unwrap();
+        assert!(stored_major_versions(&versions).is_err());
+    }
+
+    #[test]
+    fn reads_no_stored_major_versions() {
+        let temp = tempfile::tempdir().unwrap();
+        assert!(stored_major_versions(temp.path()).unwrap().is_empty());
+        assert!(stored_major_versions(&temp.path().join("missing")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+/// Obfuscation mappings in the ProGuard text format Mojang publishes, keyed by obfuscated class name.
+#[derive(Debug, Clone, Default)]
+pub struct Mappings {
+    classes: HashMap<String, ClassMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassMapping {
+    pub original: String,
+    pub obfuscated: String,
+    pub fields: Vec<FieldMapping>,
+    pub methods: Vec<MethodMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub field_type: String,
+    pub original: String,
+    pub obfuscated: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodMapping {
+    pub return_type: String,
+    pub original: String,
+    pub obfuscated: String,
+    pub arguments: Vec<String>,
+    pub lines: Option<(u32, u32)>,
+}
+
+impl Mappings {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut classes = HashMap::new();
+        let mut current: Option<ClassMapping> = None;
+
+        for (number, line) in contents.lines().enumerate() {
+            let bad_line = || Error::MappingsParseError(number + 1, line.to_owned());
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                let class = current.as_mut().ok_or_else(bad_line)?;
+                let (member, obfuscated) = line.trim().split_once(" -> ").ok_or_else(bad_line)?;
+                if member.contains('(') {
+                    class.methods.push(parse_method(member, obfuscated).ok_or_else(bad_line)?);
+                } else {
+                    let (field_type, original) = member.split_once(' ').ok_or_else(bad_line)?;
+                    class.fields.push(FieldMapping {
+                        field_type: field_type.to_owned(),
+                        original: original.to_owned(),
+                        obfuscated: obfuscated.to_owned(),
+                    });
+                }
+            } else {
+                let (original, obfuscated) = line
+                    .strip_suffix(':')
+                    .and_then(|line| line.split_once(" -> "))
+                    .ok_or_else(bad_line)?;
+                if let Some(class) = current.replace(ClassMapping {
+                    original: original.to_owned(),
+                    obfuscated: obfuscated.to_owned(),
+                    fields: vec![],
+                    methods: vec![],
+                }) {
+                    classes.insert(class.obfuscated.clone(), class);
+                }
+            }
+        }
+        if let Some(class) = current {
+            classes.insert(class.obfuscated.clone(), class);
+        }
+
+        Ok(Self { classes })
+    }
+
+    pub fn class(&self, obfuscated: &str) -> Option<&ClassMapping> {
+        self.classes.get(obfuscated)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = &ClassMapping> {
+        self.classes.values()
+    }
+
+    /// Remaps class and method names in a log or crash report, e.g.
+    /// `at efn.<init>(SourceFile:19)` becomes `at net.minecraft.client.GameNarrator.<init>(GameNarrator.java:19)`.
+    pub fn deobfuscate_stack_trace(&self, trace: &str) -> String {
+        trace
+            .split_inclusive('\n')
+            .map(|line| self.deobfuscate_line(line))
+            .collect()
+    }
+
+    fn deobfuscate_line(&self, line: &str) -> String {
+        let indent = line.len() - line.trim_start().len();
+        let (prefix, rest) = line.split_at(indent);
+
+        if let Some(frame) = rest.strip_prefix("at ") {
+            if let Some(frame) = self.deobfuscate_frame(frame) {
+                return format!("{prefix}at {frame}");
+            }
+        }
+
+        for marker in ["Caused by: ", "Exception in thread "] {
+            if let Some(index) = line.find(marker) {
+                let (head, tail) = line.split_at(index + marker.len());
+                let (tail_head, tail) = if marker == "Exception in thread " {
+                    match tail.find("\" ") {
+                        Some(end) => tail.split_at(end + 2),
+                        None => return line.to_owned(),
+                    }
+                } else {
+                    ("", tail)
+                };
+                return format!("{head}{tail_head}{}", self.deobfuscate_exception(tail));
+            }
+        }
+
+        line.to_owned()
+    }
+
+    fn deobfuscate_exception(&self, exception: &str) -> String {
+        let end = exception
+            .find(|c: char| c == ':' || c.is_whitespace())
+            .unwrap_or(exception.len());
+        let (class, rest) = exception.split_at(end);
+        match self.class(class) {
+            Some(mapping) => format!("{}{rest}", mapping.original),
+            None => exception.to_owned(),
+        }
+    }
+
+    fn deobfuscate_frame(&self, frame: &str) -> Option<String> {
+        let open = frame.find('(')?;
+        let close = open + frame[open..].find(')')?;
+        let (class, method) = frame[..open].rsplit_once('.')?;
+        let source = &frame[open + 1..close];
+        let line = source.split_once(':').and_then(|(_, line)| line.parse::<u32>().ok());
+
+        let mapping = self.class(class)?;
+        let method = mapping.original_method(method, line).unwrap_or(method);
+        let source = match source.split_once(':') {
+            Some(("SourceFile", line)) => format!("{}:{line}", mapping.source_file()),
+            None if source == "SourceFile" => mapping.source_file(),
+            _ => source.to_owned(),
+        };
+
+        Some(format!("{}.{method}({source}){}", mapping.original, &frame[close + 1..]))
+    }
+}
+
+impl ClassMapping {
+    /// Resolves an obfuscated method name, using the frame's line number to pick between overloads.
+    pub fn original_method(&self, obfuscated: &str, line: Option<u32>) -> Option<&str> {
+        let candidates = self
+            .methods
+            .iter()
+            .filter(|method| method.obfuscated == obfuscated)
+            .collect::<Vec<_>>();
+
+        if let Some(line) = line {
+            if let Some(method) = candidates
+                .iter()
+                .find(|method| method.lines.is_some_and(|(start, end)| start <= line && line <= end))
+            {
+                return Some(&method.original);
+            }
+        }
+
+        let first = candidates.first()?;
+        candidates
+            .iter()
+            .all(|method| method.original == first.original)
+            .then_some(first.original.as_str())
+    }
+
+    fn source_file(&self) -> String {
+        let simple = self.original.rsplit('.').next().unwrap_or(&self.original);
+        let outer = simple.split('$').next().unwrap_or(simple);
+        format!("{outer}.java")
+    }
+}
+
+fn parse_method(member: &str, obfuscated: &str) -> Option<MethodMapping> {
+    let mut parts = member.splitn(3, ':');
+    let (lines, signature) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(start), Some(end), Some(signature)) => (Some((start.parse().ok()?, end.parse().ok()?)), signature),
+        (Some(signature), None, None) => (None, signature),
+        _ => return None,
+    };
+
+    let (return_type, rest) = signature.split_once(' ')?;
+    let (original, arguments) = rest.split_once('(')?;
+    let arguments = arguments.split_once(')')?.0;
+
+    Some(MethodMapping {
+        return_type: return_type.to_owned(),
+        original: original.to_owned(),
+        obfuscated: obfuscated.to_owned(),
+        arguments: arguments
+            .split(',')
+            .filter(|arg| !arg.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mappings;
+
+    const MAPPINGS: &str = "# {\"fileName\":\"client.txt\"}
+net.minecraft.client.Minecraft -> efu:
+    int fps -> a
+    637:640:void <init>(net.minecraft.client.main.GameConfig) -> <init>
+    700:710:void tick() -> a
+    720:730:void runTick(boolean) -> a
+net.minecraft.client.GameNarrator -> efn:
+    19:25:void <init>(net.minecraft.client.Minecraft) -> <init>
+net.minecraft.ReportedException -> o:
+";
+
+    #[test]
+    fn parses_proguard() {
+        let mappings = Mappings::parse(MAPPINGS).unwrap();
+        let minecraft = mappings.class("efu").unwrap();
+        assert_eq!(minecraft.original, "net.minecraft.client.Minecraft");
+        assert_eq!(minecraft.fields[0].original, "fps");
+        assert_eq!(minecraft.methods[2].arguments, ["boolean"]);
+        assert_eq!(minecraft.methods[2].lines, Some((720, 730)));
+        assert_eq!(minecraft.original_method("a", Some(725)), Some("runTick"));
+        assert_eq!(minecraft.original_method("a", None), None);
+
+        assert!(Mappings::parse("    int orphan -> a\n").is_err());
+    }
+
+    #[test]
+    fn deobfuscates_stack_trace() {
+        let mappings = Mappings::parse(MAPPINGS).unwrap();
+        let trace = "Caused by: o: crashed\n\tat efn.<init>(SourceFile:19) [client.jar:?]\n\tat efu.a(SourceFile:705) [client.jar:?]\n\tat net.minecraft.client.main.Main.main(SourceFile:51) [client.jar:?]\n";
+
+        assert_eq!(
+            mappings.deobfuscate_stack_trace(trace),
+            "Caused by: net.minecraft.ReportedException: crashed\n\
+             \tat net.minecraft.client.GameNarrator.<init>(GameNarrator.java:19) [client.jar:?]\n\
+             \tat net.minecraft.client.Minecraft.tick(Minecraft.java:705) [client.jar:?]\n\
+             \tat net.minecraft.client.main.Main.main(SourceFile:51) [client.jar:?]\n"
+        );
+    }
+}
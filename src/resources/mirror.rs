@@ -0,0 +1,78 @@
+/// A download mirror, described as URL prefix rewrites of Mojang's hosts.
+///
+/// Mirrors listed in `DownloadConfig::mirrors` are tried in order; the official URL is always
+/// tried last, so a mirror that is down or serves a file with the wrong hash is skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mirror {
+    pub name: String,
+    pub rewrites: Vec<(String, String)>,
+}
+
+impl Mirror {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            rewrites: vec![],
+        }
+    }
+
+    /// Adds a rewrite of URLs starting with `from` to start with `to` instead.
+    pub fn rewrite(mut self, from: &str, to: &str) -> Self {
+        self.rewrites.push((from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// BMCLAPI, which mirrors metadata, libraries and assets under a single host.
+    pub fn bmclapi() -> Self {
+        Self::with_host("bmclapi", "https://bmclapi2.bangbang93.com")
+    }
+
+    /// A caching proxy (e.g. a LAN Nginx) that mirrors every Mojang host under one base URL,
+    /// using the same paths as BMCLAPI: `/maven` for libraries and `/assets` for assets.
+    pub fn with_host(name: &str, base: &str) -> Self {
+        let base = base.trim_end_matches('/');
+        Self::new(name)
+            .rewrite("https://launchermeta.mojang.com", base)
+            .rewrite("https://launcher.mojang.com", base)
+            .rewrite("https://piston-meta.mojang.com", base)
+            .rewrite("https://piston-data.mojang.com", base)
+            .rewrite("https://libraries.minecraft.net", &format!("{base}/maven"))
+            .rewrite("https://resources.download.minecraft.net", &format!("{base}/assets"))
+    }
+
+    pub fn apply(&self, url: &str) -> Option<String> {
+        self.rewrites
+            .iter()
+            .find_map(|(from, to)| url.strip_prefix(from.as_str()).map(|rest| format!("{to}{rest}")))
+    }
+}
+
+/// URLs to try for `url`: every matching mirror in order, then the official URL.
+pub fn candidates(url: &str, mirrors: &[Mirror]) -> Vec<String> {
+    let mut urls = mirrors
+        .iter()
+        .filter_map(|mirror| mirror.apply(url))
+        .collect::<Vec<_>>();
+    urls.push(url.to_owned());
+    urls.dedup();
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{candidates, Mirror};
+
+    #[test]
+    fn rewrites_known_hosts() {
+        let mirrors = [Mirror::with_host("lan", "http://cache.lan/"), Mirror::bmclapi()];
+        assert_eq!(
+            candidates("https://libraries.minecraft.net/org/lwjgl/lwjgl.jar", &mirrors),
+            [
+                "http://cache.lan/maven/org/lwjgl/lwjgl.jar",
+                "https://bmclapi2.bangbang93.com/maven/org/lwjgl/lwjgl.jar",
+                "https://libraries.minecraft.net/org/lwjgl/lwjgl.jar",
+            ]
+        );
+        assert_eq!(candidates("https://api.foojay.io/jdk.tar.gz", &mirrors), ["https://api.foojay.io/jdk.tar.gz"]);
+    }
+}
@@ -1,6 +1,18 @@
 pub mod version;
-mod download;
+pub mod version_id;
+pub mod mappings;
+pub mod download;
+pub mod progress;
+pub mod mirror;
+pub mod runtime;
+pub mod java;
+pub mod jdk;
+pub mod managed;
+mod archive;
+mod cache;
+mod hash;
+mod retry;
+mod limits;
 mod natives;
-mod jdk;
 
 
@@ -1,55 +1,501 @@
 use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
+use super::archive::{self, ExtractLimits};
 use super::version::Library;
-use crate::{error::Result, path_with_launcher};
-//TODO: handle pre 1.19 natives
-pub fn extract_natives(libraries: &[Library]) -> Result<()> {
-    let natives = libraries
-        .iter()
-        .filter(|library| {
-            if let Some(rules) = &library.rules {
-                let rule = &rules[0];
-                if let Some(os) = rule.os.as_ref() {
-                    #[cfg(target_os = "windows")]
-                    return os.name.as_ref().unwrap() == "windows";
-                    #[cfg(target_os = "linux")]
-                    return os.name.as_ref().unwrap() == "linux";
-                } else {
-                    return false;
-                };
-                
+use crate::{error::{Error, Result}, path_with_launcher};
+
+/// Written into every extracted directory once extraction has finished.
+const MANIFEST: &str = "manifest.json";
+
+/// Extensions of the native libraries in a 1.19+ natives jar.
+const NATIVE_EXTENSIONS: [&str; 4] = ["so", "dll", "dylib", "jnilib"];
+
+/// The top-level folders of a 1.19+ natives jar, which holds its libraries under `<os>/<arch>/`.
+const PLATFORM_FOLDERS: [&str; 3] = ["linux", "windows", "macos"];
+
+/// This platform's `<os>/<arch>` folder in a 1.19+ natives jar, e.g. `linux/x64` or `macos/arm64`.
+fn platform_folder() -> String {
+    let os = if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else {
+        "linux"
+    };
+    let arch = if cfg!(target_arch = "x86_64") {
+        "x64"
+    } else if cfg!(target_arch = "x86") {
+        "x86"
+    } else if cfg!(target_arch = "aarch64") {
+        "arm64"
+    } else if cfg!(target_arch = "arm") {
+        "arm32"
+    } else {
+        std::env::consts::ARCH
+    };
+    format!("{os}/{arch}")
+}
+
+/// A jar with natives for this platform.
+struct NativeJar<'a> {
+    /// Where the jar was downloaded to.
+    path: String,
+    sha1: &'a str,
+    /// Entry prefixes to skip, from the library's `extract.exclude`.
+    exclude: &'a [String],
+    /// Since 1.19 natives are libraries of their own, such as `org.lwjgl:lwjgl:3.3.1:natives-linux`,
+    /// with their native libraries in per-platform folders; those are extracted flat. Older
+    /// classifier jars are extracted as they are.
+    flatten: bool,
+}
+
+/// The files extracted from a natives jar, relative to the directory they were extracted to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativesManifest {
+    /// The jar the natives were extracted from.
+    pub jar: String,
+    pub files: Vec<String>,
+}
+
+impl NativeJar<'_> {
+    fn extract(&self, destination: &Path) -> Result<NativesManifest> {
+        let platform = self.flatten.then(platform_folder);
+        extract_jar(&self.path, destination, self.exclude, platform.as_deref())
+    }
+}
+
+/// Extracts the natives of the jar at `jar` into `destination`, skipping entries under one of
+/// the `exclude` prefixes, and returns what was extracted. With `platform`, the `<os>/<arch>`
+/// folder to take libraries from, only native libraries are extracted, straight into
+/// `destination`; ones in other platforms' folders are skipped. Two entries extracted to the
+/// same file are an error.
+fn extract_jar(jar: &str, destination: &Path, exclude: &[String], platform: Option<&str>) -> Result<NativesManifest> {
+    extract_jar_with(jar, destination, exclude, platform, &ExtractLimits::default())
+}
+
+/// `extract_jar`, failing with `Error::ArchiveError` once the jar exceeds `limits`.
+fn extract_jar_with(
+    jar: &str,
+    destination: &Path,
+    exclude: &[String],
+    platform: Option<&str>,
+    limits: &ExtractLimits,
+) -> Result<NativesManifest> {
+    let archive_error = |reason: String| Error::ArchiveError { path: jar.to_owned(), reason };
+    let mut archive = zip::ZipArchive::new(File::open(jar)?).map_err(|e| archive_error(e.to_string()))?;
+    if archive.len() > limits.max_entries {
+        return Err(archive_error(format!("more than {} entries", limits.max_entries)));
+    }
+
+    let mut files: Vec<String> = vec![];
+    let mut written = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| archive_error(e.to_string()))?;
+        let name = entry.name().to_owned();
+        if entry.is_dir() || exclude.iter().any(|prefix| name.starts_with(prefix.as_str())) {
+            continue;
+        }
+        let path = archive::safe_path(Path::new(&name))
+            .ok_or_else(|| archive_error(format!("entry {name} escapes the natives directory")))?;
+        let path = if let Some(platform) = platform {
+            let is_native = path
+                .extension()
+                .is_some_and(|extension| NATIVE_EXTENSIONS.iter().any(|native| extension == *native));
+            let other_platform = path
+                .components()
+                .next()
+                .is_some_and(|os| PLATFORM_FOLDERS.iter().any(|folder| os.as_os_str() == *folder))
+                && !path.starts_with(platform);
+            match path.file_name() {
+                _ if other_platform => continue,
+                Some(file_name) if is_native => PathBuf::from(file_name),
+                _ => continue,
             }
+        } else {
+            path
+        };
+        let file = path.to_string_lossy().replace('\\', "/");
+        if file == MANIFEST || files.contains(&file) {
+            return Err(archive_error(format!("entry {name} collides with {file}")));
+        }
+
+        let path = destination.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let remaining = limits.max_total_size - written;
+        let copied = std::io::copy(&mut (&mut entry).take(remaining + 1), &mut File::create(&path)?)
+            .map_err(|e| archive_error(format!("{name}: {e}")))?;
+        if copied > remaining {
+            let _ = std::fs::remove_file(&path);
+            return Err(archive_error(format!("more than {} bytes when extracted", limits.max_total_size)));
+        }
+        written += copied;
+        files.push(file);
+    }
+
+    if files.is_empty() {
+        warn!("{} has no natives to extract", jar);
+    } else {
+        info!("extracted {} natives from {}", files.len(), jar);
+    }
+    Ok(NativesManifest { jar: jar.to_owned(), files })
+}
 
-            return false;
+fn native_jars(libraries: &[Library]) -> Vec<NativeJar<'_>> {
+    libraries
+        .iter()
+        .filter(|library| library.is_allowed())
+        .filter_map(|library| {
+            let (artifact, flatten) = match (library.native_artifact(), &library.downloads.artifact) {
+                (Some(artifact), _) => (artifact, false),
+                (None, Some(artifact)) if library.name.contains(":natives-") => (artifact, true),
+                _ => return None,
+            };
+            Some(NativeJar {
+                path: artifact.local_path(),
+                sha1: &artifact.sha1,
+                exclude: library.extract_exclude(),
+                flatten,
+            })
         })
-        .map(|library| {
-            path_with_launcher("libraries/") + &library.downloads.artifact.path
-        });
-    
-    for native in natives {
-        let file = File::open(native)?;
-        let mut archive = zip::ZipArchive::new(file).unwrap();
-        let library = archive.file_names().filter(|file| file.ends_with(".so")).next().unwrap().to_owned();
-        let _ = std::fs::create_dir(path_with_launcher("natives")); //ignoring
-        let mut outfile = File::create(path_with_launcher("natives/") + library.split("/").last().unwrap())?;
-        std::io::copy(&mut archive.by_name(&library).unwrap(), &mut outfile)?;
+        .collect()
+}
+
+/// `natives/` of the launcher directory: jars are extracted under `libraries/` and launches
+/// link to them from `launches/`.
+fn natives_root() -> PathBuf {
+    PathBuf::from(path_with_launcher("natives"))
+}
+
+/// The manifest of a finished extraction into `directory`, if there is one.
+pub fn read_manifest(directory: &Path) -> Option<NativesManifest> {
+    let manifest = std::fs::read_to_string(directory.join(MANIFEST)).ok()?;
+    serde_json::from_str(&manifest).ok()
+}
+
+/// Extracts the natives this platform needs, each jar into a directory named after its sha1,
+/// and returns each directory with its manifest. Jars extracted before, by this or any other
+/// version, are skipped.
+pub fn extract_natives(libraries: &[Library]) -> Result<Vec<(PathBuf, NativesManifest)>> {
+    extract_jars(&native_jars(libraries), &natives_root().join("libraries"))
+}
+
+/// Extracts each of `jars` into `<extracted>/<sha1>` unless it already was. Versions sharing
+/// a library build share its directory.
+fn extract_jars(jars: &[NativeJar], extracted_root: &Path) -> Result<Vec<(PathBuf, NativesManifest)>> {
+    let mut extracted = vec![];
+    for jar in jars {
+        let directory = extracted_root.join(jar.sha1);
+        let manifest = match read_manifest(&directory) {
+            Some(manifest) => manifest,
+            None => extract_into(jar, &directory)?,
+        };
+        extracted.push((directory, manifest));
     }
 
+    Ok(extracted)
+}
+
+/// Extracts `jar` next to `directory` and moves it in place with its manifest, so a
+/// concurrent launch never sees a half-extracted jar.
+fn extract_into(jar: &NativeJar, directory: &Path) -> Result<NativesManifest> {
+    if directory.exists() {
+        // another launch may have just finished extracting it
+        if let Some(manifest) = read_manifest(directory) {
+            return Ok(manifest);
+        }
+        // left without a manifest by an interrupted or older launcher
+        std::fs::remove_dir_all(directory)?;
+    }
+    let partial = directory.with_extension(unique_suffix());
+    std::fs::create_dir_all(&partial)?;
+    let extracted = jar.extract(&partial).and_then(|manifest| {
+        std::fs::write(partial.join(MANIFEST), serde_json::to_string(&manifest)?)?;
+        std::fs::rename(&partial, directory)?;
+        Ok(manifest)
+    });
+    match extracted {
+        Ok(manifest) => Ok(manifest),
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&partial);
+            // another launch may have finished extracting the same jar first
+            read_manifest(directory).ok_or(e)
+        }
+    }
+}
+
+/// `<pid>-<n>`, different for every call in every running launcher.
+fn unique_suffix() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!("{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// The natives directory of a single launch, holding links to the extracted natives of every
+/// library the version needs. It is deleted when dropped, leaving the extracted natives for
+/// the next launch and any other launch's directory untouched.
+#[derive(Debug)]
+pub struct NativesDirectory {
+    path: PathBuf,
+}
+
+impl NativesDirectory {
+    /// Extracts whatever is missing and links it into `natives/launches/<version>-<pid>-<n>`.
+    pub fn create(libraries: &[Library], version: &str) -> Result<Self> {
+        Self::create_in(&natives_root(), &native_jars(libraries), version)
+    }
+
+    fn create_in(natives: &Path, jars: &[NativeJar], version: &str) -> Result<Self> {
+        let extracted = extract_jars(jars, &natives.join("libraries"))?;
+        let launches = natives.join("launches");
+        sweep_launches(&launches);
+        let path = launches.join(format!("{version}-{}", unique_suffix()));
+        std::fs::create_dir_all(&path)?;
+        let directory = Self { path };
+        for (source, manifest) in extracted {
+            for file in &manifest.files {
+                link(&source.join(file), &directory.path.join(file))?;
+            }
+        }
+        Ok(directory)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for NativesDirectory {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!("could not remove natives directory {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Removes the directories under `launches` left behind by launchers that are no longer
+/// running, such as ones that crashed or were killed before dropping their `NativesDirectory`.
+fn sweep_launches(launches: &Path) {
+    let Ok(entries) = std::fs::read_dir(launches) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let pid = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| {
+                // `<version>-<pid>-<n>`
+                let mut parts = name.rsplit('-');
+                parts.next()?.parse::<usize>().ok()?;
+                parts.next()?.parse::<u32>().ok()
+            });
+        match pid {
+            Some(pid) if !is_running(pid) => {
+                if let Err(e) = std::fs::remove_dir_all(&path) {
+                    warn!("could not remove stale natives directory {}: {}", path.display(), e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether a process with id `pid` is running. Without a way to tell, every process is
+/// assumed to be running, so nothing is swept.
+fn is_running(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// Hard links `from` to `to`, copying when linking fails. A file already at `to`, from
+/// another library, is an error rather than silently replaced.
+fn link(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::symlink_metadata(to).is_ok() {
+        return Err(Error::ArchiveError {
+            path: from.display().to_string(),
+            reason: format!("{} is provided by more than one natives jar", to.display()),
+        });
+    }
+    if std::fs::hard_link(from, to).is_err() {
+        std::fs::copy(from, to)?;
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
+    use super::{ExtractLimits, NativeJar, NativesDirectory};
     use crate::resources::version::get_available_versions;
 
     #[tokio::test]
     #[tracing_test::traced_test]
     async fn gets_natives() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         let details = latest.get_details().await.unwrap();
         super::extract_natives(&details.libraries).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn extracts_classifier_jars() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let jar = dir.join("lwjgl-platform-natives-linux.jar");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&jar).unwrap());
+        for (name, contents) in [("liblwjgl64.so", "lwjgl"), ("libopenal64.so", "openal"), ("META-INF/MANIFEST.MF", "manifest")] {
+            writer.start_file(name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let natives = dir.join("natives");
+        let manifest = super::extract_jar(jar.to_str().unwrap(), &natives, &[String::from("META-INF/")], None).unwrap();
+        assert_eq!(manifest.files, ["liblwjgl64.so", "libopenal64.so"]);
+        assert_eq!(std::fs::read_to_string(natives.join("liblwjgl64.so")).unwrap(), "lwjgl");
+        assert_eq!(std::fs::read_to_string(natives.join("libopenal64.so")).unwrap(), "openal");
+        assert!(!natives.join("META-INF").exists());
+
+        std::fs::write(dir.join("broken.jar"), "not a jar").unwrap();
+        let broken = super::extract_jar(dir.join("broken.jar").to_str().unwrap(), &natives, &[], None);
+        assert!(matches!(broken, Err(crate::error::Error::ArchiveError { .. })));
+    }
+
+    fn write_jar(path: &std::path::Path, entries: &[&str]) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for name in entries {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn extracts_every_native_library() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let jar = dir.join("lwjgl-3.3.1-natives-linux.jar");
+        write_jar(&jar, &[
+            "META-INF/MANIFEST.MF",
+            "META-INF/lib.so",
+            "linux/x64/org/lwjgl/liblwjgl.so",
+            "linux/x64/org/lwjgl/liblwjgl.so.sha1",
+            "linux/x64/org/lwjgl/opengl/liblwjgl_opengl.so",
+            "linux/arm64/org/lwjgl/liblwjgl.so",
+            "windows/x64/org/lwjgl/lwjgl.dll",
+            "org/lwjgl/Version.class",
+            "libglfw.so",
+        ]);
+
+        let natives = dir.join("natives");
+        let manifest = super::extract_jar(jar.to_str().unwrap(), &natives, &[String::from("META-INF/")], Some("linux/x64")).unwrap();
+        assert_eq!(manifest.files, ["liblwjgl.so", "liblwjgl_opengl.so", "libglfw.so"]);
+        assert_eq!(std::fs::read_to_string(natives.join("liblwjgl_opengl.so")).unwrap(), "linux/x64/org/lwjgl/opengl/liblwjgl_opengl.so");
+        assert!(!natives.join("Version.class").exists());
+
+        let empty = dir.join("empty.jar");
+        write_jar(&empty, &["META-INF/MANIFEST.MF"]);
+        let manifest = super::extract_jar(empty.to_str().unwrap(), &natives, &[], Some("linux/x64")).unwrap();
+        assert!(manifest.files.is_empty());
+
+        let colliding = dir.join("colliding.jar");
+        write_jar(&colliding, &["linux/x64/org/lwjgl/liblwjgl.so", "linux/x64/liblwjgl.so"]);
+        let collided = super::extract_jar(colliding.to_str().unwrap(), &dir.join("colliding"), &[], Some("linux/x64"));
+        assert!(matches!(collided, Err(crate::error::Error::ArchiveError { .. })));
+
+        let traversal = dir.join("traversal.jar");
+        write_jar(&traversal, &["../liblwjgl.so"]);
+        let escaped = super::extract_jar(traversal.to_str().unwrap(), &natives, &[], Some("linux/x64"));
+        assert!(matches!(escaped, Err(crate::error::Error::ArchiveError { .. })));
+    }
+
+    #[test]
+    fn extracts_within_limits() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let jar = dir.join("bomb.jar");
+        write_jar(&jar, &["liblwjgl64.so", "libopenal64.so"]);
+        let jar = jar.to_str().unwrap();
+
+        let few_entries = ExtractLimits { max_entries: 1, ..Default::default() };
+        let extracted = super::extract_jar_with(jar, &dir.join("entries"), &[], None, &few_entries);
+        assert!(matches!(extracted, Err(crate::error::Error::ArchiveError { .. })));
+        let small = ExtractLimits { max_total_size: 20, ..Default::default() };
+        let extracted = super::extract_jar_with(jar, &dir.join("size"), &[], None, &small);
+        assert!(matches!(extracted, Err(crate::error::Error::ArchiveError { .. })));
+        assert!(!dir.join("size/libopenal64.so").exists());
+        let enough = ExtractLimits { max_entries: 2, max_total_size: 27 };
+        assert_eq!(super::extract_jar_with(jar, &dir.join("ok"), &[], None, &enough).unwrap().files.len(), 2);
+    }
+
+    #[test]
+    fn separates_concurrent_launches() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_owned();
+        let jar = dir.join("natives.jar");
+        write_jar(&jar, &["liblwjgl64.so"]);
+        let native_jar = |path: &std::path::Path, sha1| NativeJar {
+            path: path.to_string_lossy().into_owned(),
+            sha1,
+            exclude: &[],
+            flatten: false,
+        };
+        let jars = [native_jar(&jar, "aa")];
+        let natives = dir.join("natives");
+
+        let first = NativesDirectory::create_in(&natives, &jars, "1.12.2").unwrap();
+        let second = NativesDirectory::create_in(&natives, &jars, "1.12.2").unwrap();
+        assert_ne!(first.path(), second.path());
+        let extracted = super::extract_jars(&jars, &natives.join("libraries")).unwrap();
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted[0].0, natives.join("libraries/aa"));
+        assert_eq!(super::read_manifest(&extracted[0].0).unwrap().files, ["liblwjgl64.so"]);
+
+        let first_path = first.path().to_owned();
+        drop(first);
+        assert!(!first_path.exists());
+        assert_eq!(std::fs::read_to_string(second.path().join("liblwjgl64.so")).unwrap(), "liblwjgl64.so");
+        drop(second);
+        assert!(natives.join("libraries/aa/liblwjgl64.so").exists());
+
+        let duplicate = dir.join("duplicate.jar");
+        write_jar(&duplicate, &["liblwjgl64.so"]);
+        let jars = [native_jar(&jar, "aa"), native_jar(&duplicate, "bb")];
+        let collided = NativesDirectory::create_in(&natives, &jars, "1.12.2");
+        assert!(matches!(collided, Err(crate::error::Error::ArchiveError { .. })));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sweeps_stale_launches() {
+        let temp = tempfile::tempdir().unwrap();
+        let launches = temp.path().to_owned();
+        let running = launches.join(format!("1.19-pre1-{}-0", std::process::id()));
+        let stale = launches.join(format!("1.19-pre1-{}-0", u32::MAX));
+        let unrelated = launches.join("custom");
+        for directory in [&running, &stale, &unrelated] {
+            std::fs::create_dir_all(directory).unwrap();
+        }
+
+        super::sweep_launches(&launches);
+        assert!(running.exists());
+        assert!(!stale.exists());
+        assert!(unrelated.exists());
+    }
+}
@@ -0,0 +1,82 @@
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{Error, Result};
+
+/// Steps of a full version install, in the order `VersionDetails::install` runs them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStage {
+    Client,
+    Libraries,
+    Assets,
+    Java,
+    Natives,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    JobStarted { total_files: usize, total_bytes: u64 },
+    StageStarted(InstallStage),
+    FileProgress { path: String, downloaded: u64, total: Option<u64> },
+    FileDone { path: String, size: u64 },
+    FileSkipped { path: String, size: Option<u64> },
+    FileFailed { path: String, error: String },
+    JobDone { failed: usize },
+}
+
+/// Where download jobs report their progress. `Progress::none()` discards every event.
+///
+/// A job can also be stopped through it: once the token given to `with_cancellation`
+/// is cancelled, no new files are started, files in flight are aborted and their
+/// partial downloads deleted, and the job fails with `Error::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    sender: Option<UnboundedSender<ProgressEvent>>,
+    cancellation: CancellationToken,
+}
+
+impl Progress {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn channel() -> (Self, UnboundedReceiver<ProgressEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self { sender: Some(sender), ..Default::default() }, receiver)
+    }
+
+    pub fn stream() -> (Self, UnboundedReceiverStream<ProgressEvent>) {
+        let (progress, receiver) = Self::channel();
+        (progress, UnboundedReceiverStream::new(receiver))
+    }
+
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self { cancellation, ..self }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Fails with `Error::Cancelled` once the job has been cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolves when the job is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        self.cancellation.cancelled().await
+    }
+
+    pub(crate) fn send(&self, event: ProgressEvent) {
+        if let Some(sender) = &self.sender {
+            // the receiver going away just means nobody is watching anymore
+            let _ = sender.send(event);
+        }
+    }
+}
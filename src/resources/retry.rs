@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::{header, Response, StatusCode};
+
+use crate::error::Error;
+
+/// How failed downloads are retried. Delays grow exponentially from `base_delay`,
+/// capped at `max_delay`, with random jitter so parallel downloads don't retry in lockstep.
+/// A server's `Retry-After` hint is honoured up to `max_delay` too.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retrying after `attempt` (starting at 1) failed with `error`,
+    /// or `None` when the error is not worth retrying or attempts are exhausted.
+    pub fn delay_after(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let backoff = || {
+            let exponential = self
+                .base_delay
+                .saturating_mul(2u32.saturating_pow(attempt - 1))
+                .min(self.max_delay);
+            let half = exponential / 2;
+            half + half.mul_f64(rand::thread_rng().gen::<f64>())
+        };
+
+        match error {
+            Error::HttpStatus { status, retry_after, .. } if *status == StatusCode::TOO_MANY_REQUESTS.as_u16() => {
+                // never wait longer than the policy allows, whatever the server asks for
+                Some(retry_after.map_or_else(backoff, |retry_after| retry_after.min(self.max_delay)))
+            }
+            Error::HttpStatus { status, .. } if *status >= 500 || *status == StatusCode::REQUEST_TIMEOUT.as_u16() => {
+                Some(backoff())
+            }
+            Error::Timeout(_) => Some(backoff()),
+            Error::NetworkError(e) if e.is_timeout() || e.is_connect() || e.is_body() || e.is_request() => Some(backoff()),
+            // a short body usually means the connection dropped mid-transfer
+            Error::SizeMismatch { .. } => Some(backoff()),
+            _ => None,
+        }
+    }
+}
+
+/// Turns a non-success response into `Error::HttpStatus`, keeping any `Retry-After` hint.
+pub fn check_status(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    Err(Error::HttpStatus {
+        url: response.url().to_string(),
+        status: status.as_u16(),
+        retry_after,
+    })
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_retry_after, RetryPolicy};
+    use crate::error::Error;
+
+    fn status(status: u16, retry_after: Option<Duration>) -> Error {
+        Error::HttpStatus { url: String::new(), status, retry_after }
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let policy = RetryPolicy::default();
+        assert!(policy.delay_after(1, &status(503, None)).is_some());
+        assert!(policy.delay_after(1, &status(404, None)).is_none());
+        assert!(policy.delay_after(4, &status(503, None)).is_none());
+        assert_eq!(
+            policy.delay_after(1, &status(429, Some(Duration::from_secs(7)))),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            policy.delay_after(1, &status(429, Some(Duration::from_secs(86400)))),
+            Some(policy.max_delay)
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..4 {
+            let delay = policy.delay_after(attempt, &status(500, None)).unwrap();
+            let ceiling = policy.base_delay * 2u32.pow(attempt - 1);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "{delay:?} for attempt {attempt}");
+        }
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
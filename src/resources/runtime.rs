@@ -0,0 +1,341 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{error::{Error, Result}, path_with_launcher};
+
+use super::{archive, cache};
+use super::java::{self, JavaSource};
+use super::download::{self, Download, DownloadLzma, DownloadReport, DownloadType, DownloadWithHashCheck, Downloadeable};
+use super::progress::Progress;
+
+const RUNTIMES_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+/// Mojang's name for the platform this launcher runs on, as used in the runtime index.
+pub fn platform() -> Option<&'static str> {
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Some("linux")
+    } else if cfg!(all(target_os = "linux", target_arch = "x86")) {
+        Some("linux-i386")
+    } else if cfg!(all(target_os = "macos", target_arch = "x86_64")) {
+        Some("mac-os")
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        Some("mac-os-arm64")
+    } else if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
+        Some("windows-x64")
+    } else if cfg!(all(target_os = "windows", target_arch = "x86")) {
+        Some("windows-x86")
+    } else if cfg!(all(target_os = "windows", target_arch = "aarch64")) {
+        Some("windows-arm64")
+    } else {
+        None
+    }
+}
+
+/// `all.json`: platform → component → available builds.
+type RuntimeIndex = HashMap<String, HashMap<String, Vec<RuntimeEntry>>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RuntimeEntry {
+    manifest: FileDownload,
+    version: RuntimeVersion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RuntimeVersion {
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileDownload {
+    sha1: String,
+    size: usize,
+    url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RuntimeManifest {
+    files: BTreeMap<String, RuntimeFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum RuntimeFile {
+    Directory,
+    File {
+        #[serde(default)]
+        executable: bool,
+        downloads: RuntimeFileDownloads,
+    },
+    Link {
+        target: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RuntimeFileDownloads {
+    raw: FileDownload,
+    lzma: Option<FileDownload>,
+}
+
+/// A Java runtime from Mojang's `java-runtime` manifests, installed file by file into
+/// `runtime/<component>/<platform>`, exactly as the vanilla launcher does.
+#[derive(Debug, Clone)]
+pub struct JavaRuntime {
+    pub component: String,
+    pub platform: String,
+    /// Runtime version, e.g. `17.0.8`.
+    pub version: String,
+    files: BTreeMap<String, RuntimeFile>,
+}
+
+/// One regular file of a runtime, fetched compressed when Mojang offers an LZMA copy.
+pub struct RuntimeFileDownload {
+    path: String,
+    downloads: RuntimeFileDownloads,
+}
+
+impl Downloadeable for RuntimeFileDownload {
+    fn download_info(&self) -> DownloadType {
+        let raw = &self.downloads.raw;
+        match &self.downloads.lzma {
+            Some(lzma) => DownloadType::Lzma(DownloadLzma {
+                download: Download { path: self.path.clone(), url: lzma.url.clone() },
+                compressed_size: lzma.size,
+                compressed_sha1: lzma.sha1.clone(),
+                size: raw.size,
+                sha1: raw.sha1.clone(),
+            }),
+            None => DownloadType::HashCheck(DownloadWithHashCheck {
+                download: Download { path: self.path.clone(), url: raw.url.clone() },
+                size: raw.size,
+                sha1: raw.sha1.clone(),
+            }),
+        }
+    }
+}
+
+impl JavaRuntime {
+    /// Looks up `component` (e.g. `java-runtime-gamma`) for this platform. `Ok(None)` means
+    /// Mojang doesn't publish that runtime here and another JDK source has to be used.
+    pub async fn find(component: &str) -> Result<Option<Self>> {
+        let Some(platform) = platform() else {
+            return Ok(None);
+        };
+        let mut index: RuntimeIndex = cache::fetch_json(RUNTIMES_URL, &path_with_launcher("runtime/all.json"), None).await?;
+        let Some(entry) = index
+            .remove(platform)
+            .and_then(|mut components| components.remove(component))
+            .and_then(|entries| entries.into_iter().next())
+        else {
+            return Ok(None);
+        };
+
+        let manifest_path = path_with_launcher(&format!("runtime/{component}/{platform}.json"));
+        let manifest: RuntimeManifest = cache::fetch_json(&entry.manifest.url, &manifest_path, Some(&entry.manifest.sha1)).await?;
+        validate(&manifest.files).map_err(|reason| Error::ArchiveError { path: manifest_path, reason })?;
+
+        Ok(Some(Self {
+            component: component.to_owned(),
+            platform: platform.to_owned(),
+            version: entry.version.name,
+            files: manifest.files,
+        }))
+    }
+
+    pub fn directory(&self) -> String {
+        runtime_directory(&self.component, &self.platform)
+    }
+
+    /// Every regular file of the runtime.
+    pub fn files(&self) -> Vec<RuntimeFileDownload> {
+        self.files
+            .iter()
+            .filter_map(|(path, file)| match file {
+                RuntimeFile::File { downloads, .. } => Some(RuntimeFileDownload {
+                    path: format!("{}/{path}", self.directory()),
+                    downloads: downloads.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Downloads the runtime's files, then marks executables and creates its links.
+    /// Links and permissions are only set up once every file is in place. Installing over an
+    /// older build of the component upgrades it in place.
+    pub async fn install(&self, progress: &Progress) -> Result<DownloadReport> {
+        let directory = self.directory();
+        for (path, file) in &self.files {
+            if let RuntimeFile::Directory = file {
+                tokio::fs::create_dir_all(format!("{directory}/{path}")).await?;
+            }
+        }
+
+        let report = download::download_items(&self.files(), progress).await;
+        if report.is_complete() {
+            self.remove_stale(Path::new(&directory))?;
+            self.finish(&directory)?;
+            info!("installed {} {} into {}", self.component, self.version, directory);
+            java::register(&self.java_executable(), JavaSource::Mojang).await?;
+        }
+        Ok(report)
+    }
+
+    fn java_executable(&self) -> PathBuf {
+        let directory = PathBuf::from(self.directory());
+        if cfg!(target_os = "macos") {
+            directory.join("jre.bundle/Contents/Home/bin/java")
+        } else if cfg!(target_os = "windows") {
+            directory.join("bin/java.exe")
+        } else {
+            directory.join("bin/java")
+        }
+    }
+
+    /// Deletes whatever the manifest doesn't list, such as files of a previous build.
+    fn remove_stale(&self, directory: &Path) -> Result<()> {
+        for path in stale_entries(directory, Path::new(""), &self.files)? {
+            if path.symlink_metadata()?.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self, directory: &str) -> Result<()> {
+        for (path, file) in &self.files {
+            let path = Path::new(directory).join(path);
+            match file {
+                RuntimeFile::File { executable: true, .. } => archive::set_mode(&path, Some(0o755))?,
+                RuntimeFile::Link { target } => {
+                    if path.symlink_metadata().is_ok() {
+                        std::fs::remove_file(&path)?;
+                    }
+                    archive::symlink(Path::new(target), &path)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that every file of a manifest stays inside the runtime directory: paths are plain
+/// relative paths, nothing is placed below a link, and links resolve inside the runtime
+/// without passing through other links.
+fn validate(files: &BTreeMap<String, RuntimeFile>) -> std::result::Result<(), String> {
+    let is_link = |path: &Path| {
+        let key = path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        matches!(files.get(&key), Some(RuntimeFile::Link { .. }))
+    };
+    for (key, file) in files {
+        let path = Path::new(key);
+        if archive::safe_path(path).as_deref() != Some(path) {
+            return Err(format!("{key} escapes the runtime directory"));
+        }
+        if path.ancestors().skip(1).any(|ancestor| !ancestor.as_os_str().is_empty() && is_link(ancestor)) {
+            return Err(format!("{key} is inside a link"));
+        }
+        if let RuntimeFile::Link { target } = file {
+            if archive::resolve_link(path, Path::new(target), is_link).is_none() {
+                return Err(format!("link {key} -> {target} escapes the runtime directory"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Entries under `directory/relative` that `files` doesn't list. Unlisted directories are
+/// returned whole rather than descended into.
+fn stale_entries(directory: &Path, relative: &Path, files: &BTreeMap<String, RuntimeFile>) -> Result<Vec<PathBuf>> {
+    let mut stale = vec![];
+    for entry in std::fs::read_dir(directory.join(relative))? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        let key = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        match files.get(&key) {
+            None => stale.push(directory.join(&relative)),
+            Some(RuntimeFile::Directory) if entry.file_type()?.is_dir() => {
+                stale.extend(stale_entries(directory, &relative, files)?)
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(stale)
+}
+
+fn runtime_directory(component: &str, platform: &str) -> String {
+    path_with_launcher(&format!("runtime/{component}/{platform}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stale_entries, validate, RuntimeFile, RuntimeManifest};
+
+    #[test]
+    fn parses_runtime_manifest() {
+        let manifest: RuntimeManifest = serde_json::from_str(r#"{"files": {
+            "bin": {"type": "directory"},
+            "bin/java": {"type": "file", "executable": true, "downloads": {
+                "lzma": {"sha1": "aa", "size": 10, "url": "https://piston-data.mojang.com/java.lzma"},
+                "raw": {"sha1": "bb", "size": 20, "url": "https://piston-data.mojang.com/java"}
+            }},
+            "legal/java.base/LICENSE": {"type": "link", "target": "../java.base/LICENSE"}
+        }}"#).unwrap();
+
+        assert!(matches!(manifest.files["bin"], RuntimeFile::Directory));
+        match &manifest.files["bin/java"] {
+            RuntimeFile::File { executable, downloads } => {
+                assert!(executable);
+                assert_eq!(downloads.lzma.as_ref().unwrap().size, 10);
+            }
+            _ => panic!("expected a file"),
+        }
+        assert!(matches!(&manifest.files["legal/java.base/LICENSE"], RuntimeFile::Link { target } if target == "../java.base/LICENSE"));
+    }
+
+    #[test]
+    fn finds_stale_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path().to_owned();
+        for dir in ["bin", "lib/old"] {
+            std::fs::create_dir_all(directory.join(dir)).unwrap();
+        }
+        for file in ["bin/java", "bin/jfr", "lib/modules", "lib/old/libjsig.so", "release"] {
+            std::fs::write(directory.join(file), "").unwrap();
+        }
+        let manifest: RuntimeManifest = serde_json::from_str(r#"{"files": {
+            "bin": {"type": "directory"},
+            "bin/java": {"type": "file", "downloads": {"raw": {"sha1": "aa", "size": 0, "url": ""}}},
+            "lib": {"type": "directory"},
+            "lib/modules": {"type": "file", "downloads": {"raw": {"sha1": "bb", "size": 0, "url": ""}}}
+        }}"#).unwrap();
+
+        let mut stale = stale_entries(&directory, std::path::Path::new(""), &manifest.files).unwrap();
+        stale.sort();
+        let expected: Vec<_> = ["bin/jfr", "lib/old", "release"].iter().map(|path| directory.join(path)).collect();
+        assert_eq!(stale, expected);
+    }
+
+    #[test]
+    fn rejects_escaping_manifests() {
+        let manifest = |files: &str| serde_json::from_str::<RuntimeManifest>(&format!(r#"{{"files": {{{files}}}}}"#)).unwrap().files;
+        let file = r#"{"type": "file", "downloads": {"raw": {"sha1": "aa", "size": 0, "url": ""}}}"#;
+
+        assert!(validate(&manifest(&format!(r#""bin/java": {file}, "lib/java": {{"type": "link", "target": "../bin/java"}}"#))).is_ok());
+        assert!(validate(&manifest(&format!(r#""../bin/java": {file}"#))).is_err());
+        assert!(validate(&manifest(&format!(r#""/etc/passwd": {file}"#))).is_err());
+        assert!(validate(&manifest(r#""legal": {"type": "link", "target": "../../.."}"#)).is_err());
+        assert!(validate(&manifest(&format!(r#""lib": {{"type": "link", "target": ".."}}, "lib/java": {file}"#))).is_err());
+        assert!(validate(&manifest(r#""d/b": {"type": "link", "target": ".."}, "d/a": {"type": "link", "target": "b/../.."}"#)).is_err());
+    }
+}
@@ -1,13 +1,20 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use glob::glob;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::path_with_launcher;
-use crate::{client, error::Result};
-use crate::resources::download::DownloadStatus;
+use crate::{error::{AssetIndexError, Error, Result}};
+use crate::resources::progress::{InstallStage, Progress, ProgressEvent};
 
-use super::download::{self, Downloadeable, DownloadWithSizeCheck, DownloadType};
+use super::{cache, hash, version_id};
+use super::java::{self, JavaInstallation, JavaPolicy, JavaSource};
+use super::natives::NativesDirectory;
+use super::runtime::JavaRuntime;
+use super::mappings::Mappings;
+use super::download::{self, DownloadReport, Downloadeable, DownloadWithHashCheck, DownloadType, Verification};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", untagged)]
@@ -56,11 +63,14 @@ pub struct Version {
     pub url: String,
     pub time: chrono::DateTime<chrono::Utc>,
     pub release_time: chrono::DateTime<chrono::Utc>,
+    pub sha1: String,
 }
 
 impl Version {
+    /// Loads the version JSON through the cache, checking it against the manifest SHA-1.
     pub async fn get_details(&self) -> Result<VersionDetails> {
-        Ok(client.get(&self.url).send().await?.json().await?)
+        let path = path_with_launcher("versions/") + &self.id + "/" + &self.id + ".json";
+        cache::fetch_json(&self.url, &path, Some(&self.sha1)).await
     }
 }
 
@@ -76,7 +86,11 @@ pub enum VersionType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VersionDetails {
-    pub arguments: Arguments,
+    pub id: String,
+    /// Since 1.13; older versions have `minecraft_arguments` instead.
+    pub arguments: Option<Arguments>,
+    /// The game arguments of versions before 1.13, as one space-separated string.
+    pub minecraft_arguments: Option<String>,
     asset_index: AssetIndex,
     pub assets: String,
     downloads: Downloads,
@@ -165,13 +179,26 @@ pub struct JavaVersion {
 pub struct Library {
     pub name: String,
     pub downloads: LibraryDownload,
-    pub rules: Option<Vec<PlatformRule>>
+    pub rules: Option<Vec<PlatformRule>>,
+    /// OS name → key into `downloads.classifiers` of the jar holding this library's natives,
+    /// e.g. `"windows": "natives-windows-${arch}"`. Only used up to 1.18.
+    pub natives: Option<HashMap<String, String>>,
+    pub extract: Option<LibraryExtract>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LibraryDownload {
-    pub artifact: LibraryArtifact,
+    pub artifact: Option<LibraryArtifact>,
+    pub classifiers: Option<HashMap<String, LibraryArtifact>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryExtract {
+    /// Entry prefixes to skip when extracting the natives jar, e.g. `META-INF/`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -183,28 +210,93 @@ pub struct LibraryArtifact {
     pub url: String,
 }
 
+/// This platform's name in version rules and `natives` maps.
+fn os_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "osx"
+    } else {
+        "linux"
+    }
+}
+
+/// Whether `rules` allow a library on `os`/`arch`. Like the vanilla launcher: no rules means
+/// allowed; otherwise the last matching rule decides and nothing matching means disallowed.
+fn rules_allow(rules: &[PlatformRule], os: &str, arch: &str) -> bool {
+    rules
+        .iter()
+        .rfind(|rule| match &rule.os {
+            Some(rule_os) => {
+                rule_os.name.as_deref().is_none_or(|name| name == os)
+                    && rule_os.arch.as_deref().is_none_or(|rule_arch| rule_arch == arch)
+            }
+            None => true,
+        })
+        .map_or(rules.is_empty(), |rule| rule.action == "allow")
+}
+
+impl Library {
+    /// Whether this library is used on this platform.
+    pub fn is_allowed(&self) -> bool {
+        let arch = if cfg!(target_arch = "x86") { "x86" } else { std::env::consts::ARCH };
+        self.rules.as_deref().is_none_or(|rules| rules_allow(rules, os_name(), arch))
+    }
+
+    /// The classifier jar with this library's natives for this platform, for versions up to 1.18.
+    pub fn native_artifact(&self) -> Option<&LibraryArtifact> {
+        let bits = if cfg!(target_pointer_width = "32") { "32" } else { "64" };
+        self.native_artifact_for(os_name(), bits)
+    }
+
+    fn native_artifact_for(&self, os: &str, bits: &str) -> Option<&LibraryArtifact> {
+        let classifier = self.natives.as_ref()?.get(os)?.replace("${arch}", bits);
+        self.downloads.classifiers.as_ref()?.get(&classifier)
+    }
+
+    /// Entry prefixes that must not be extracted from the natives jar.
+    pub fn extract_exclude(&self) -> &[String] {
+        self.extract.as_ref().map_or(&[], |extract| extract.exclude.as_slice())
+    }
+
+    /// Every file this library needs on this platform: its jar and, before 1.19, its natives jar.
+    pub fn artifacts(&self) -> Vec<&LibraryArtifact> {
+        if !self.is_allowed() {
+            return vec![];
+        }
+        self.downloads.artifact.iter().chain(self.native_artifact()).collect()
+    }
+}
+
+impl LibraryArtifact {
+    pub fn local_path(&self) -> String {
+        path_with_launcher("libraries/") + &self.path
+    }
+}
+
 pub async fn get_available_versions() -> Result<Versions> {
-    Ok(client
-        .get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
-        .send()
-        .await?
-        .json()
-        .await?)
+    cache::fetch_json(
+        "https://launchermeta.mojang.com/mc/game/version_manifest_v2.json",
+        &path_with_launcher("versions/version_manifest_v2.json"),
+        None,
+    )
+    .await
 }
 
 impl Versions {
-    pub fn latest_release(&self) -> &Version {
+    pub fn get(&self, id: &str) -> Result<&Version> {
         self.versions
             .iter()
-            .find(|v| v.id == self.latest.release)
-            .unwrap()
+            .find(|v| v.id == id)
+            .ok_or_else(|| Error::VersionNotFound(id.to_owned()))
     }
 
-    pub fn latest_snapshot(&self) -> &Version {
-        self.versions
-            .iter()
-            .find(|v| v.id == self.latest.snapshot)
-            .unwrap()
+    pub fn latest_release(&self) -> Result<&Version> {
+        self.get(&self.latest.release)
+    }
+
+    pub fn latest_snapshot(&self) -> Result<&Version> {
+        self.get(&self.latest.snapshot)
     }
 
     pub fn all(&self) -> &Vec<Version> {
@@ -227,6 +319,54 @@ impl Versions {
         self.filter_by_version_type(VersionType::OldAlpha)
     }
 
+    /// Versions released between `from` and `to`, both included, newest first.
+    pub fn between(&self, from: &str, to: &str) -> Result<Vec<&Version>> {
+        let from = self.get(from)?.release_time;
+        let to = self.get(to)?.release_time;
+        let (start, end) = if from <= to { (from, to) } else { (to, from) };
+        Ok(self
+            .versions
+            .iter()
+            .filter(|v| v.release_time >= start && v.release_time <= end)
+            .collect())
+    }
+
+    pub fn releases_after(&self, date: chrono::DateTime<chrono::Utc>) -> Vec<&Version> {
+        self.release()
+            .into_iter()
+            .filter(|v| v.release_time > date)
+            .collect()
+    }
+
+    /// Snapshots, pre-releases and release candidates published after the previous release
+    /// and up to `release_id`.
+    pub fn snapshots_for(&self, release_id: &str) -> Result<Vec<&Version>> {
+        let release = self.get(release_id)?;
+        let previous = self
+            .release()
+            .into_iter()
+            .filter(|v| v.release_time < release.release_time)
+            .map(|v| v.release_time)
+            .max();
+        Ok(self
+            .snapshot()
+            .into_iter()
+            .filter(|v| {
+                v.release_time <= release.release_time
+                    && previous.is_none_or(|previous| v.release_time > previous)
+            })
+            .collect())
+    }
+
+    /// Orders two versions by id, falling back to release times when the ids alone
+    /// can't tell (e.g. a weekly snapshot against a release).
+    pub fn compare(&self, a: &str, b: &str) -> Result<Ordering> {
+        if let Some(ordering) = version_id::compare_ids(a, b) {
+            return Ok(ordering);
+        }
+        Ok(self.get(a)?.release_time.cmp(&self.get(b)?.release_time))
+    }
+
     fn filter_by_version_type(&self, version_type: VersionType) -> Vec<&Version> {
         self.versions
             .iter()
@@ -235,6 +375,12 @@ impl Versions {
     }
 }
 
+/// Where a missing Java runtime will be installed from.
+enum JdkSource {
+    Mojang(JavaRuntime),
+    Foojay(super::jdk::JavaVersion),
+}
+
 impl VersionDetails {
     fn index_path(&self) -> String {
         path_with_launcher("assets/indexes/") + &self.assets + ".json"
@@ -246,127 +392,390 @@ impl VersionDetails {
         Ok(())
     }
 
-    pub async fn download_jdk(&self) -> Result<()> {
-        super::jdk::JavaVersion::search(self.java_version.major_version)
-            .await?
-            .download_info()
-            .download()
-            .await?;
+    /// Installs the Java runtime this version asks for: Mojang's own runtime for
+    /// `java_version.component` when one exists for this platform, otherwise a foojay JDK.
+    pub async fn download_jdk(&self, progress: &Progress) -> Result<()> {
+        if self.find_installed_jdk().await {
+            return Ok(());
+        }
+        match self.find_jdk().await? {
+            JdkSource::Mojang(runtime) => {
+                runtime.install(progress).await?.into_result()?;
+            }
+            JdkSource::Foojay(jdk) => {
+                progress.check_cancelled()?;
+                let status = jdk.download_info().download_with(progress).await?;
+                self.register_foojay_jdk(status.extracted_to.as_deref()).await?;
+            }
+        }
         Ok(())
     }
 
-    pub async fn download_assets<F>(&self, f: F) -> Result<()>
-        where F: FnOnce(&download::Download, &Result<DownloadStatus>) + Clone {
+    /// Registers the JDK foojay just extracted, failing if it isn't the major version we need.
+    async fn register_foojay_jdk(&self, root: Option<&Path>) -> Result<()> {
+        let root = root.ok_or(Error::JavaVersionNotFoundError)?;
+        let installation = java::register_root(root, JavaSource::Foojay).await?;
+        if installation.major_version as i32 == self.java_version.major_version {
+            Ok(())
+        } else {
+            Err(Error::JavaVersionNotFoundError)
+        }
+    }
+
+    async fn find_jdk(&self) -> Result<JdkSource> {
+        if !self.java_version.component.is_empty() {
+            if let Some(runtime) = JavaRuntime::find(&self.java_version.component).await? {
+                return Ok(JdkSource::Mojang(runtime));
+            }
+            warn!("no Mojang runtime {} for this platform, using foojay", self.java_version.component);
+        }
+        Ok(JdkSource::Foojay(super::jdk::JavaVersion::search(self.java_version.major_version).await?))
+    }
+
+    /// Downloads every asset, failing with `Error::IncompleteDownload` if any of them failed.
+    pub async fn download_assets(&self, progress: &Progress) -> Result<DownloadReport> {
         let assets = self.assets().await?;
-        download::download_collection(&assets, |d, r| {
-            download::log_download(d, r);
-           f(d, r)
-        }).await;
-        Ok(())
+        download::download_collection(&assets, progress).await.into_result()
+    }
+
+    /// Library jars this platform needs, including pre-1.19 natives jars.
+    pub fn library_artifacts(&self) -> Vec<LibraryArtifact> {
+        self.libraries.iter().flat_map(Library::artifacts).cloned().collect()
+    }
+
+    pub async fn download_libraries(&self, progress: &Progress) -> Result<DownloadReport> {
+        download::download_collection(&self.library_artifacts(), progress).await.into_result()
+    }
+
+    /// Installs everything needed to launch this version (client, libraries, assets, natives
+    /// and, if missing, the JDK) as a single job reported through `progress`.
+    /// Fails with `Error::IncompleteDownload` if any file could not be downloaded.
+    pub async fn install(&self, progress: &Progress) -> Result<DownloadReport> {
+        let client_download = self.client_download_info();
+        let library_artifacts = self.library_artifacts();
+        let libraries = library_artifacts.iter().map(|library| library.download_info()).collect::<Vec<_>>();
+        let assets = self.assets().await?;
+        let asset_downloads = assets.iter().map(|asset| asset.download_info()).collect::<Vec<_>>();
+        let jdk = if self.find_installed_jdk().await {
+            None
+        } else {
+            Some(self.find_jdk().await?)
+        };
+        progress.check_cancelled()?;
+
+        let (mut total_files, mut total_bytes) = download::totals(
+            std::iter::once(&client_download).chain(&libraries).chain(&asset_downloads)
+        );
+        match &jdk {
+            Some(JdkSource::Mojang(runtime)) => {
+                let runtime_files = runtime.files().iter().map(|file| file.download_info()).collect::<Vec<_>>();
+                let (files, bytes) = download::totals(&runtime_files);
+                total_files += files;
+                total_bytes += bytes;
+            }
+            Some(JdkSource::Foojay(jdk)) => {
+                total_files += 1;
+                total_bytes += jdk.size().unwrap_or(0);
+            }
+            None => {}
+        }
+        progress.send(ProgressEvent::JobStarted { total_files, total_bytes });
+
+        let mut report = DownloadReport::default();
+        let result = self
+            .install_stages(&client_download, &library_artifacts, &assets, jdk.as_ref(), &mut report, progress)
+            .await;
+        // a download interrupted by the cancellation is recorded as failed, not cancelled
+        report.cancelled |= progress.is_cancelled();
+
+        progress.send(ProgressEvent::JobDone { failed: report.failed.len() });
+        match result {
+            Err(e) if !report.cancelled => Err(e),
+            _ => report.into_result(),
+        }
     }
 
-    pub async fn download_libraries<F>(&self, f: F) -> Result<()>
-        where F: FnOnce(&download::Download, &Result<DownloadStatus>) + Clone  {
-        download::download_collection(&self.libraries, |d, r| {
-            download::log_download(d, r);
-            f(d, r)
-        }).await;
+    /// The stages of `install`, recording every download in `report`.
+    async fn install_stages(
+        &self,
+        client_download: &DownloadType,
+        library_artifacts: &[LibraryArtifact],
+        assets: &[(String, Asset)],
+        jdk: Option<&JdkSource>,
+        report: &mut DownloadReport,
+        progress: &Progress,
+    ) -> Result<()> {
+        progress.send(ProgressEvent::StageStarted(InstallStage::Client));
+        let client_result = download::download_item(client_download, progress).await;
+        report.record(client_download.inner().path.clone(), client_result);
+
+        progress.send(ProgressEvent::StageStarted(InstallStage::Libraries));
+        report.merge(download::download_items(library_artifacts, progress).await);
+
+        progress.send(ProgressEvent::StageStarted(InstallStage::Assets));
+        report.merge(download::download_items(assets, progress).await);
+
+        if let Some(jdk) = jdk {
+            progress.send(ProgressEvent::StageStarted(InstallStage::Java));
+            match jdk {
+                JdkSource::Mojang(runtime) => report.merge(runtime.install(progress).await?),
+                JdkSource::Foojay(jdk) => {
+                    let jdk_download = jdk.download_info();
+                    let jdk_result = download::download_item(&jdk_download, progress).await;
+                    let extracted_to = jdk_result.as_ref().ok().and_then(|status| status.extracted_to.clone());
+                    report.record(jdk_download.inner().path.clone(), jdk_result);
+                    if let Some(root) = extracted_to {
+                        self.register_foojay_jdk(Some(&root)).await?;
+                    }
+                }
+            }
+        }
+
+        if report.is_complete() && !progress.is_cancelled() {
+            progress.send(ProgressEvent::StageStarted(InstallStage::Natives));
+            self.extract_natives()?;
+        }
         Ok(())
     }
 
-    pub fn check_jdk(&self) -> bool {
-        glob(&(path_with_launcher("jdk/") + "*" + &self.java_version.major_version.to_string() +  "*/bin/java")).unwrap().count() != 0
+    /// Whether a registered runtime, or failing that a system one found by a
+    /// discovery scan, can run this version.
+    async fn find_installed_jdk(&self) -> bool {
+        if java::java_policy() == JavaPolicy::PreferSystem || !self.check_jdk().await {
+            java::register_system_once().await;
+        }
+        self.check_jdk().await
+    }
+
+    pub async fn check_jdk(&self) -> bool {
+        self.java_installation().await.is_some()
+    }
+
+    /// The registered Java installation matching this version's major version exactly. JDKs
+    /// already under `jdk/` are registered on the first lookup.
+    pub async fn java_installation(&self) -> Option<JavaInstallation> {
+        java::register_foojay_once().await;
+        let major_version = self.java_version.major_version.try_into().ok()?;
+        match java::find(major_version) {
+            Ok(installation) => installation,
+            Err(e) => {
+                warn!("could not read the java registry: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn java_path(&self) -> Option<PathBuf> {
+        self.java_installation().await.map(|installation| installation.path)
     }
 
     pub async fn check_assets(&self) -> bool {
-        super::download::is_downloaded(&self.assets().await.unwrap())
+        match self.assets().await {
+            Ok(assets) => download::is_downloaded(&assets, Verification::Size),
+            Err(_) => false,
+        }
     }
 
     pub async fn check_libraries(&self) -> bool {
-        super::download::is_downloaded(&self.libraries)
+        download::is_downloaded(&self.library_artifacts(), Verification::Size)
     }
 
     pub async fn check_client(&self) -> bool {
-        self.client_download_info().size_check().unwrap().check_size()
+        self.client_download_info().is_present(Verification::Size)
     }
 
-    pub fn extract_natives(&self) -> Result<()> {
-        super::natives::extract_natives(&self.libraries)
+    /// Hashes the client, libraries and assets, returning the paths that are missing or corrupt.
+    pub async fn verify_files(&self) -> Result<Vec<String>> {
+        let assets = self.assets().await?;
+        let mut invalid = download::find_invalid(&self.library_artifacts(), Verification::Hash);
+        invalid.append(&mut download::find_invalid(&assets, Verification::Hash));
+        if !self.client_download_info().is_present(Verification::Hash) {
+            invalid.push(self.client_download_info().inner().path.clone());
+        }
+        Ok(invalid)
     }
 
-    async fn assets(&self) -> Result<Vec<(String, Asset)>> {
-        return if let Ok(assets) = self.load_asset_index().await {
-            Ok(assets)
-        } else {
-            self.store_asset_index().await?;
-            self.load_asset_index().await
+    pub async fn download_server(&self) -> Result<()> {
+        self.server_download_info().download().await?;
+        Ok(())
+    }
+
+    pub async fn check_server(&self) -> bool {
+        self.server_download_info().is_present(Verification::Size)
+    }
+
+    pub fn server_path(&self) -> String {
+        path_with_launcher("server/") + &self.id + "/server.jar"
+    }
+
+    pub async fn download_client_mappings(&self) -> Result<()> {
+        self.mappings_download_info("client", &self.downloads.client_mappings)?.download().await?;
+        Ok(())
+    }
+
+    pub async fn download_server_mappings(&self) -> Result<()> {
+        self.mappings_download_info("server", &self.downloads.server_mappings)?.download().await?;
+        Ok(())
+    }
+
+    /// Loads the client mappings, downloading them first if they are not present.
+    pub async fn client_mappings(&self) -> Result<Mappings> {
+        self.load_mappings("client", &self.downloads.client_mappings).await
+    }
+
+    pub async fn server_mappings(&self) -> Result<Mappings> {
+        self.load_mappings("server", &self.downloads.server_mappings).await
+    }
+
+    async fn load_mappings(&self, side: &str, mappings: &Option<Download>) -> Result<Mappings> {
+        let download_info = self.mappings_download_info(side, mappings)?;
+        if !download_info.is_present(Verification::Size) {
+            download_info.download().await?;
         }
+        let contents = tokio::fs::read_to_string(self.mappings_path(side)).await?;
+        Mappings::parse(&contents)
     }
 
-    async fn load_asset_index(&self) -> Result<Vec<(String, Asset)>> {
-        let index = tokio::fs::read_to_string(self.index_path()).await?;
-        let assets = serde_json::from_str::<HashMap<String, HashMap<String, Asset>>>(&index).unwrap().remove("objects").unwrap();
-        Ok(assets.into_iter().collect())
+    fn mappings_path(&self, side: &str) -> String {
+        path_with_launcher("mappings/") + &self.id + "/" + side + ".txt"
     }
 
-    async fn store_asset_index(&self) -> Result<()> {
-        let response = client
-            .get(&self.asset_index.url)
-            .send()
-            .await?
-            .text()
-            .await?;
-        
-        
-        tokio::fs::create_dir_all(path_with_launcher("assets/indexes")).await?;
-        tokio::fs::write(self.index_path(), response).await?;
+    /// Extracts the natives this version needs, skipping jars extracted before.
+    pub fn extract_natives(&self) -> Result<()> {
+        super::natives::extract_natives(&self.libraries)?;
         Ok(())
     }
 
+    /// A natives directory for one launch of this version, removed when it's dropped.
+    pub(crate) fn natives_directory(&self) -> Result<NativesDirectory> {
+        NativesDirectory::create(&self.libraries, &self.id)
+    }
+
+    async fn assets(&self) -> Result<Vec<(String, Asset)>> {
+        let index = match tokio::fs::read(self.index_path()).await {
+            Ok(index) if self.check_asset_index(&index).is_ok() => index,
+            Ok(_) => {
+                warn!("asset index {} does not match its sha1 or size, fetching it again", self.asset_index.id);
+                self.store_asset_index().await?
+            }
+            Err(_) => self.store_asset_index().await?,
+        };
+        parse_asset_index(&index)
+    }
+
+    fn check_asset_index(&self, index: &[u8]) -> Result<()> {
+        if index.len() != self.asset_index.size as usize {
+            return Err(Error::AssetIndexError(AssetIndexError::SizeMismatch {
+                expected: self.asset_index.size as usize,
+                actual: index.len(),
+            }));
+        }
+        hash::check_sha1(&self.index_path(), index, &self.asset_index.sha1)
+    }
+
+    /// Downloads the asset index like any other checked file, through the configured
+    /// mirrors, retries and limits, and returns its contents.
+    async fn store_asset_index(&self) -> Result<Vec<u8>> {
+        DownloadType::HashCheck(DownloadWithHashCheck {
+            download: download::Download {
+                path: self.index_path(),
+                url: self.asset_index.url.clone(),
+            },
+            size: self.asset_index.size as usize,
+            sha1: self.asset_index.sha1.clone(),
+        })
+        .download()
+        .await?;
+        Ok(tokio::fs::read(self.index_path()).await?)
+    }
+
     fn client_download_info(&self) -> DownloadType {
-        DownloadType::SizeCheck(
-            DownloadWithSizeCheck {
+        DownloadType::HashCheck(
+            DownloadWithHashCheck {
                     download: download::Download {
                         path: path_with_launcher("client/") + &self.assets + "/client.jar",
                         url: self.downloads.client.url.clone()
                     },
-                    size: self.downloads.client.size as usize
+                    size: self.downloads.client.size as usize,
+                    sha1: self.downloads.client.sha1.clone()
+            }
+        )
+    }
+
+    fn mappings_download_info(&self, side: &str, mappings: &Option<Download>) -> Result<DownloadType> {
+        let mappings = mappings
+            .as_ref()
+            .ok_or_else(|| Error::MappingsNotAvailable(self.id.clone()))?;
+        Ok(DownloadType::HashCheck(
+            DownloadWithHashCheck {
+                    download: download::Download {
+                        path: self.mappings_path(side),
+                        url: mappings.url.clone()
+                    },
+                    size: mappings.size as usize,
+                    sha1: mappings.sha1.clone()
+            }
+        ))
+    }
+
+    fn server_download_info(&self) -> DownloadType {
+        DownloadType::HashCheck(
+            DownloadWithHashCheck {
+                    download: download::Download {
+                        path: self.server_path(),
+                        url: self.downloads.server.url.clone()
+                    },
+                    size: self.downloads.server.size as usize,
+                    sha1: self.downloads.server.sha1.clone()
             }
         )
     }
 }
 
+#[derive(Deserialize)]
+struct AssetIndexObjects {
+    objects: HashMap<String, Asset>,
+}
+
+fn parse_asset_index(index: &[u8]) -> Result<Vec<(String, Asset)>> {
+    let index = serde_json::from_slice::<AssetIndexObjects>(index).map_err(AssetIndexError::Parse)?;
+    Ok(index.objects.into_iter().collect())
+}
+
 impl Downloadeable for (String, Asset) {
     fn download_info(&self) -> DownloadType {
-        DownloadType::SizeCheck(DownloadWithSizeCheck {
+        DownloadType::HashCheck(DownloadWithHashCheck {
             download: download::Download {
                 path:  path_with_launcher("assets/objects/") + &self.1.hash[..2] + "/" + &self.1.hash,
-                url: format!("http://resources.download.minecraft.net/{}/{}", &self.1.hash[..2], &self.1.hash)
+                url: format!("https://resources.download.minecraft.net/{}/{}", &self.1.hash[..2], &self.1.hash)
             },
-            size: self.1.size as usize
+            size: self.1.size as usize,
+            sha1: self.1.hash.clone()
         })
     }
 }
 
 
-impl Downloadeable for Library {
+impl Downloadeable for LibraryArtifact {
     fn download_info(&self) -> DownloadType {
-        DownloadType::SizeCheck(DownloadWithSizeCheck {
+        DownloadType::HashCheck(DownloadWithHashCheck {
             download: download::Download {
-                path: path_with_launcher("libraries/") +  &self.downloads.artifact.path,
-                url: self.downloads.artifact.url.clone()
+                path: self.local_path(),
+                url: self.url.clone()
             },
-            size: self.downloads.artifact.size as usize
+            size: self.size as usize,
+            sha1: self.sha1.clone()
         })
     }
 }
 
 #[cfg(test)]
-mod tests {
-    use super::VersionType;
+pub(crate) mod tests {
+    use super::{rules_allow, Library, VersionDetails, VersionType, Versions};
     use tracing::info;
     use tracing_test::traced_test;
     use crate::resources::download;
+    use crate::resources::progress::Progress;
 
     use super::get_available_versions;
 
@@ -391,7 +800,7 @@ mod tests {
     #[traced_test]
     async fn latest_release() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         info!("{:#?}", latest);
         assert_eq!(latest.id, versions.latest.release);
     }
@@ -400,7 +809,7 @@ mod tests {
     #[traced_test]
     async fn latest_snapshot() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_snapshot();
+        let latest = versions.latest_snapshot().unwrap();
         info!("{:#?}", latest);
         assert_eq!(latest.id, versions.latest.snapshot);
     }
@@ -409,7 +818,7 @@ mod tests {
     #[traced_test]
     async fn get_details_for_latest_version() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         let details = latest.get_details().await.unwrap();
         info!("{:#?}", details);
     }
@@ -418,8 +827,139 @@ mod tests {
     #[traced_test]
     async fn download_assets() {
         let versions = get_available_versions().await.unwrap();
-        let latest = versions.latest_release();
+        let latest = versions.latest_release().unwrap();
         let details = latest.get_details().await.unwrap();
-        details.download_assets(|_, _| {}).await.unwrap();
+        details.download_assets(&Progress::none()).await.unwrap();
+    }
+
+    fn fixture() -> Versions {
+        let version = |id: &str, version_type: &str, date: &str| {
+            format!(r#"{{"id":"{id}","type":"{version_type}","url":"","time":"{date}T00:00:00+00:00","releaseTime":"{date}T00:00:00+00:00","sha1":""}}"#)
+        };
+        let versions = [
+            version("1.19", "release", "2022-06-07"),
+            version("1.19-rc1", "snapshot", "2022-06-02"),
+            version("1.19-pre1", "snapshot", "2022-05-18"),
+            version("22w14a", "snapshot", "2022-04-06"),
+            version("1.18.2", "release", "2022-02-28"),
+            version("22w03a", "snapshot", "2022-01-19"),
+            version("1.18", "release", "2021-11-30"),
+        ];
+        serde_json::from_str(&format!(
+            r#"{{"latest":{{"release":"1.19","snapshot":"1.19-rc1"}},"versions":[{}]}}"#,
+            versions.join(",")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn gets_by_id() {
+        let versions = fixture();
+        assert_eq!(versions.get("1.18.2").unwrap().id, "1.18.2");
+        assert!(matches!(versions.get("0.0"), Err(crate::error::Error::VersionNotFound(_))));
+        assert_eq!(versions.latest_snapshot().unwrap().id, "1.19-rc1");
+    }
+
+    #[test]
+    fn range_queries() {
+        let versions = fixture();
+        fn ids(versions: Vec<&super::Version>) -> Vec<&str> {
+            versions.into_iter().map(|v| v.id.as_str()).collect()
+        }
+
+        assert_eq!(ids(versions.between("1.19-pre1", "1.18.2").unwrap()), ["1.19-pre1", "22w14a", "1.18.2"]);
+        assert_eq!(ids(versions.releases_after("2022-01-01T00:00:00Z".parse().unwrap())), ["1.19", "1.18.2"]);
+        assert_eq!(ids(versions.snapshots_for("1.19").unwrap()), ["1.19-rc1", "1.19-pre1", "22w14a"]);
+        assert_eq!(ids(versions.snapshots_for("1.18.2").unwrap()), ["22w03a"]);
+    }
+
+    #[test]
+    fn compares_mixed_ids() {
+        let versions = fixture();
+        assert_eq!(versions.compare("22w14a", "1.19").unwrap(), std::cmp::Ordering::Less);
+        assert_eq!(versions.compare("1.19-pre1", "1.19-rc1").unwrap(), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn rejects_truncated_asset_index() {
+        let index = br#"{"objects":{"icons/icon_16x16.png":{"hash":"bdf48ef6b5d0d23bbb02e17d04865216179f510a","size":3665}}}"#;
+        assert_eq!(super::parse_asset_index(index).unwrap().len(), 1);
+        assert!(matches!(
+            super::parse_asset_index(&index[..40]),
+            Err(crate::error::Error::AssetIndexError(crate::error::AssetIndexError::Parse(_)))
+        ));
+    }
+
+    /// Details shaped like 1.12.2's, which predate `arguments`.
+    pub(crate) fn legacy_details() -> VersionDetails {
+        serde_json::from_str(r#"{
+            "assetIndex": {"id": "1.12", "sha1": "1584b57c1d0b4e0b0dce1c0c4d0f7f8fbb3a8e4b", "size": 143138, "totalSize": 129336389, "url": "https://launchermeta.mojang.com/mc/assets/1.12/1584b57c1d0b4e0b0dce1c0c4d0f7f8fbb3a8e4b/1.12.json"},
+            "assets": "1.12",
+            "downloads": {
+                "client": {"sha1": "0f275bc1547d01fa5f56ba34bdc87d981ee12daf", "size": 10180113, "url": "https://launcher.mojang.com/v1/objects/0f275bc1547d01fa5f56ba34bdc87d981ee12daf/client.jar"},
+                "server": {"sha1": "886945bfb2b978778c3a0288fd7fab09d315b25f", "size": 30222121, "url": "https://launcher.mojang.com/v1/objects/886945bfb2b978778c3a0288fd7fab09d315b25f/server.jar"}
+            },
+            "id": "1.12.2",
+            "javaVersion": {"component": "jre-legacy", "majorVersion": 8},
+            "libraries": [
+                {"name": "com.mojang:patchy:1.3.9", "downloads": {"artifact": {"path": "com/mojang/patchy/1.3.9/patchy-1.3.9.jar", "sha1": "eb8bb7b66fa0e2152b1b40b3856e82f7619439ee", "size": 23581, "url": "https://libraries.minecraft.net/com/mojang/patchy/1.3.9/patchy-1.3.9.jar"}}},
+                {"name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209", "downloads": {"classifiers": {
+                    "natives-linux": {"path": "org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-linux.jar", "sha1": "931074f46c795d2f7b30ed6395df5715cfd7675b", "size": 578680, "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-linux.jar"}
+                }}, "extract": {"exclude": ["META-INF/"]}, "natives": {"linux": "natives-linux", "osx": "natives-osx", "windows": "natives-windows"}}
+            ],
+            "logging": {"client": {"argument": "-Dlog4j.configurationFile=${path}", "type": "log4j2-xml"}},
+            "mainClass": "net.minecraft.client.main.Main",
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --versionType ${version_type}",
+            "minimumLauncherVersion": 18,
+            "releaseTime": "2017-09-18T08:39:46+00:00",
+            "time": "2017-09-18T08:39:46+00:00",
+            "type": "release"
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn parses_legacy_version_details() {
+        let details = legacy_details();
+        assert!(details.arguments.is_none());
+        assert!(details.minecraft_arguments.unwrap().starts_with("--username ${auth_player_name}"));
+        assert_eq!(details.java_version.major_version, 8);
+        assert_eq!(details.libraries[1].extract_exclude(), ["META-INF/"]);
+    }
+
+    #[test]
+    fn selects_native_classifiers() {
+        let library: Library = serde_json::from_str(r#"{
+            "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+            "downloads": {"classifiers": {
+                "natives-linux": {"path": "lwjgl-platform-natives-linux.jar", "sha1": "aa", "size": 1, "url": ""},
+                "natives-windows-64": {"path": "lwjgl-platform-natives-windows-64.jar", "sha1": "bb", "size": 1, "url": ""}
+            }},
+            "natives": {"linux": "natives-linux", "windows": "natives-windows-${arch}"},
+            "extract": {"exclude": ["META-INF/"]}
+        }"#).unwrap();
+
+        assert!(library.downloads.artifact.is_none());
+        assert_eq!(library.native_artifact_for("linux", "64").unwrap().path, "lwjgl-platform-natives-linux.jar");
+        assert_eq!(library.native_artifact_for("windows", "64").unwrap().path, "lwjgl-platform-natives-windows-64.jar");
+        assert!(library.native_artifact_for("windows", "32").is_none());
+        assert!(library.native_artifact_for("osx", "64").is_none());
+        assert_eq!(library.extract_exclude(), ["META-INF/"]);
+    }
+
+    #[test]
+    fn evaluates_library_rules() {
+        let rules = serde_json::from_str::<Vec<super::PlatformRule>>(
+            r#"[{"action": "allow"}, {"action": "disallow", "os": {"name": "osx"}}]"#,
+        ).unwrap();
+        assert!(rules_allow(&rules, "linux", "x86_64"));
+        assert!(!rules_allow(&rules, "osx", "aarch64"));
+
+        let rules = serde_json::from_str::<Vec<super::PlatformRule>>(
+            r#"[{"action": "allow", "os": {"name": "linux"}}, {"action": "disallow", "os": {"arch": "x86"}}]"#,
+        ).unwrap();
+        assert!(rules_allow(&rules, "linux", "x86_64"));
+        assert!(!rules_allow(&rules, "linux", "x86"));
+        assert!(!rules_allow(&rules, "windows", "x86_64"));
+        assert!(rules_allow(&[], "windows", "x86_64"));
     }
 }
@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+
+/// A parsed Minecraft version id, ordered the way Mojang releases them.
+///
+/// Releases, pre-releases and release candidates of the same line are totally ordered
+/// (`1.19-pre1` < `1.19-rc1` < `1.19` < `1.19.1`), as are weekly snapshots (`22w14a`).
+/// Weekly snapshots and releases can't be compared by id alone, so `partial_cmp`
+/// returns `None` for them; `Versions::compare` falls back to release times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionId {
+    Release { numbers: Vec<u32>, stage: Stage },
+    Snapshot { year: u32, week: u32, letter: char },
+    Other(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    PreRelease(u32),
+    ReleaseCandidate(u32),
+    Final,
+}
+
+impl VersionId {
+    pub fn parse(id: &str) -> Self {
+        Self::parse_release(id)
+            .or_else(|| Self::parse_snapshot(id))
+            .unwrap_or_else(|| VersionId::Other(id.to_owned()))
+    }
+
+    fn parse_release(id: &str) -> Option<Self> {
+        let (base, stage) = if let Some((base, n)) = id.split_once(" Pre-Release ") {
+            (base, Stage::PreRelease(n.parse().ok()?))
+        } else if let Some((base, suffix)) = id.split_once('-') {
+            let stage = if let Some(n) = suffix.strip_prefix("pre") {
+                Stage::PreRelease(n.parse().ok()?)
+            } else if let Some(n) = suffix.strip_prefix("rc") {
+                Stage::ReleaseCandidate(n.parse().ok()?)
+            } else {
+                return None;
+            };
+            (base, stage)
+        } else {
+            (id, Stage::Final)
+        };
+
+        let mut numbers = base
+            .split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        if numbers.len() < 2 {
+            return None;
+        }
+        while numbers.len() > 2 && numbers.last() == Some(&0) {
+            numbers.pop();
+        }
+
+        Some(VersionId::Release { numbers, stage })
+    }
+
+    fn parse_snapshot(id: &str) -> Option<Self> {
+        let (year, rest) = id.split_once('w')?;
+        let mut chars = rest.chars();
+        let letter = chars.next_back()?;
+        if year.len() != 2 || rest.len() != 3 || !letter.is_ascii_lowercase() {
+            return None;
+        }
+
+        Some(VersionId::Snapshot {
+            year: year.parse().ok()?,
+            week: chars.as_str().parse().ok()?,
+            letter,
+        })
+    }
+}
+
+impl PartialOrd for VersionId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (
+                VersionId::Release { numbers, stage },
+                VersionId::Release { numbers: other_numbers, stage: other_stage },
+            ) => Some(numbers.cmp(other_numbers).then(stage.cmp(other_stage))),
+            (
+                VersionId::Snapshot { year, week, letter },
+                VersionId::Snapshot { year: other_year, week: other_week, letter: other_letter },
+            ) => Some((year, week, letter).cmp(&(other_year, other_week, other_letter))),
+            (VersionId::Other(id), VersionId::Other(other_id)) if id == other_id => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Compares two version ids, or returns `None` when their order can't be told from the ids.
+pub fn compare_ids(a: &str, b: &str) -> Option<Ordering> {
+    VersionId::parse(a).partial_cmp(&VersionId::parse(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare_ids, Stage, VersionId};
+
+    #[test]
+    fn parses_ids() {
+        assert_eq!(VersionId::parse("1.18"), VersionId::Release { numbers: vec![1, 18], stage: Stage::Final });
+        assert_eq!(
+            VersionId::parse("1.19-pre1"),
+            VersionId::Release { numbers: vec![1, 19], stage: Stage::PreRelease(1) }
+        );
+        assert_eq!(
+            VersionId::parse("1.14 Pre-Release 2"),
+            VersionId::Release { numbers: vec![1, 14], stage: Stage::PreRelease(2) }
+        );
+        assert_eq!(VersionId::parse("22w14a"), VersionId::Snapshot { year: 22, week: 14, letter: 'a' });
+        assert_eq!(VersionId::parse("b1.7.3"), VersionId::Other(String::from("b1.7.3")));
+    }
+
+    #[test]
+    fn orders_release_line() {
+        let ordered = ["1.18", "1.18.1", "1.18.2", "1.19-pre1", "1.19-pre5", "1.19-rc1", "1.19-rc2", "1.19", "1.19.1"];
+        for pair in ordered.windows(2) {
+            assert_eq!(compare_ids(pair[0], pair[1]), Some(Ordering::Less), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(compare_ids("1.19", "1.19.0"), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn orders_snapshots() {
+        assert_eq!(compare_ids("22w14a", "22w13a"), Some(Ordering::Greater));
+        assert_eq!(compare_ids("21w44a", "22w03a"), Some(Ordering::Less));
+        assert_eq!(compare_ids("22w11a", "22w11b"), Some(Ordering::Less));
+        assert_eq!(compare_ids("22w14a", "1.19"), None);
+    }
+}
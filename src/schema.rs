@@ -1,3 +1,5 @@
+#![allow(non_local_definitions)]
+
 table! {
     accounts (id) {
         id -> Nullable<Integer>,
@@ -7,3 +9,20 @@ table! {
         username -> Text,
     }
 }
+
+table! {
+    java_installations (id) {
+        id -> Nullable<Integer>,
+        path -> Text,
+        vendor -> Text,
+        version -> Text,
+        major_version -> Integer,
+        arch -> Text,
+        source -> Text,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    accounts,
+    java_installations,
+);
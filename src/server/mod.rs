@@ -0,0 +1,152 @@
+mod properties;
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use convert_case::Casing;
+use tracing::info;
+
+pub use properties::ServerProperties;
+
+use crate::{
+    error::{Error, GameProfileError, Result, StartupRequirement},
+    path_with_launcher,
+    resources::version::VersionDetails,
+};
+
+/// A dedicated server living in `servers/<name>` under the launcher root.
+pub struct ServerInstance {
+    name: String,
+    directory: PathBuf,
+    details: VersionDetails,
+}
+
+impl ServerInstance {
+    pub fn new(version: &VersionDetails, name: &str) -> Self {
+        Self::with_directory(version, name, path_with_launcher("servers/") + &name.to_case(convert_case::Case::Camel))
+    }
+
+    pub fn with_directory(version: &VersionDetails, name: &str, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_owned(),
+            directory: directory.into(),
+            details: version.to_owned(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Accepts the Minecraft EULA for this server. Only call this after the user agreed to it.
+    pub fn accept_eula(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(
+            self.directory.join("eula.txt"),
+            "#By changing the setting below to TRUE you are indicating your agreement to our EULA (https://aka.ms/MinecraftEULA).\neula=true\n",
+        )?;
+        Ok(())
+    }
+
+    pub fn eula_accepted(&self) -> bool {
+        std::fs::read_to_string(self.directory.join("eula.txt"))
+            .map(|eula| ServerProperties::parse(&eula).get("eula") == Some("true"))
+            .unwrap_or(false)
+    }
+
+    /// Reads `server.properties`, generating the defaults if the file does not exist yet.
+    pub fn properties(&self) -> Result<ServerProperties> {
+        let path = self.directory.join("server.properties");
+        if path.exists() {
+            Ok(ServerProperties::parse(&std::fs::read_to_string(path)?))
+        } else {
+            let properties = ServerProperties::default();
+            self.save_properties(&properties)?;
+            Ok(properties)
+        }
+    }
+
+    pub fn save_properties(&self, properties: &ServerProperties) -> Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(self.directory.join("server.properties"), properties.to_string())?;
+        Ok(())
+    }
+
+    pub fn set_property(&self, key: &str, value: &str) -> Result<()> {
+        let mut properties = self.properties()?;
+        properties.set(key, value);
+        self.save_properties(&properties)
+    }
+
+    pub async fn check_requirements(&self) -> Result<()> {
+        if !self.details.check_server().await {
+            Err(Error::GameProfileError(
+                GameProfileError::RequirementFailed(StartupRequirement::Server),
+            ))
+        } else if !self.details.check_jdk().await {
+            Err(Error::GameProfileError(
+                GameProfileError::RequirementFailed(StartupRequirement::Java),
+            ))
+        } else if !self.eula_accepted() {
+            Err(Error::GameProfileError(
+                GameProfileError::RequirementFailed(StartupRequirement::Eula),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks the requirements and builds the command that runs the server in its directory.
+    async fn command(&self, jvm_args: &[&str]) -> Result<tokio::process::Command> {
+        self.check_requirements().await?;
+        self.properties()?;
+
+        info!("starting server {} ({})", self.name, self.details.id);
+        let java = self.details.java_path().await.ok_or(Error::JavaVersionNotFoundError)?;
+        let mut command = tokio::process::Command::new(java);
+        command
+            .args(jvm_args)
+            .arg("-jar")
+            .arg(self.details.server_path())
+            .arg("nogui")
+            .current_dir(&self.directory);
+        Ok(command)
+    }
+
+    /// Starts the server with its console on piped stdin/stdout, so it can be sent `stop`.
+    /// The caller has to keep reading stdout, or the server blocks once the pipe is full.
+    pub async fn start(&self, jvm_args: &[&str]) -> Result<tokio::process::Child> {
+        Ok(self.command(jvm_args).await?.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?)
+    }
+
+    /// Runs the server on this process' console until it exits.
+    pub async fn run(&self, jvm_args: &[&str]) -> Result<()> {
+        let _ = self.command(jvm_args).await?.stdin(Stdio::inherit()).stdout(Stdio::inherit()).spawn()?.wait().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::ServerInstance;
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn run() {
+        let versions = crate::resources::version::get_available_versions()
+            .await
+            .unwrap();
+        let latest = versions.latest_release().unwrap().get_details().await.unwrap();
+        latest.download_server().await.unwrap();
+
+        let server = ServerInstance::new(&latest, "test server");
+        server.accept_eula().unwrap();
+        server.set_property("server-port", "25566").unwrap();
+        assert!(server.eula_accepted());
+        server.check_requirements().await.unwrap();
+    }
+}
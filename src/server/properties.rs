@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+/// A `server.properties` file. Comments and key order are kept when editing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerProperties {
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Line {
+    Property(String, String),
+    Other(String),
+}
+
+impl ServerProperties {
+    pub fn parse(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                if trimmed.starts_with('#') || trimmed.starts_with('!') {
+                    return Line::Other(line.to_owned());
+                }
+                match line.split_once('=') {
+                    Some((key, value)) => Line::Property(key.trim().to_owned(), value.trim().to_owned()),
+                    None => Line::Other(line.to_owned()),
+                }
+            })
+            .collect();
+        Self { lines }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| match line {
+            Line::Property(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        let existing = self.lines.iter_mut().find_map(|line| match line {
+            Line::Property(k, v) if k == key => Some(v),
+            _ => None,
+        });
+        match existing {
+            Some(v) => *v = value.to_owned(),
+            None => self.lines.push(Line::Property(key.to_owned(), value.to_owned())),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.lines.retain(|line| !matches!(line, Line::Property(k, _) if k == key));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Property(k, v) => Some((k.as_str(), v.as_str())),
+            Line::Other(_) => None,
+        })
+    }
+}
+
+impl Default for ServerProperties {
+    fn default() -> Self {
+        Self::parse(
+            "#Minecraft server properties\n\
+             motd=A Minecraft Server\n\
+             server-port=25565\n\
+             online-mode=true\n\
+             max-players=20\n\
+             difficulty=easy\n\
+             gamemode=survival\n\
+             level-name=world\n\
+             view-distance=10\n",
+        )
+    }
+}
+
+impl Display for ServerProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Property(k, v) => writeln!(f, "{k}={v}")?,
+                Line::Other(other) => writeln!(f, "{other}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServerProperties;
+
+    #[test]
+    fn edits_keep_comments_and_order() {
+        let mut properties = ServerProperties::parse("#comment\nmotd=hello\nserver-port=25565\n");
+        assert_eq!(properties.get("server-port"), Some("25565"));
+
+        properties.set("server-port", "25566");
+        properties.set("pvp", "false");
+        properties.remove("motd");
+
+        assert_eq!(properties.to_string(), "#comment\nserver-port=25566\npvp=false\n");
+    }
+}
@@ -5,8 +5,9 @@ use diesel::prelude::*;
 use crate::embedded_migrations;
 use crate::error::{Result};
 use crate::path_with_launcher;
-use crate::store::models::Account;
+use crate::store::models::{Account, JavaInstallation};
 use crate::schema::accounts::dsl::*;
+use crate::schema::java_installations::dsl as java;
 
 fn establish_connection() -> Result<SqliteConnection> {
     let r = std::fs::create_dir_all(&crate::config.launcher_path);
@@ -35,4 +36,21 @@ pub fn store_account(account: &Account) -> Result<()> {
         .values(account)
         .execute(&establish_connection()?)?;
     Ok(())
-}
\ No newline at end of file
+}
+pub fn get_java_installations() -> Result<Vec<JavaInstallation>> {
+    Ok(java::java_installations.load::<JavaInstallation>(&establish_connection()?)?)
+}
+
+/// Records an installation, replacing whatever was registered at the same path.
+pub fn store_java_installation(installation: &JavaInstallation) -> Result<()> {
+    diesel::replace_into(java::java_installations)
+        .values(installation)
+        .execute(&establish_connection()?)?;
+    Ok(())
+}
+
+pub fn remove_java_installation(java_path: &str) -> Result<()> {
+    diesel::delete(java::java_installations.filter(java::path.eq(java_path)))
+        .execute(&establish_connection()?)?;
+    Ok(())
+}
@@ -1,6 +1,8 @@
+#![allow(non_local_definitions)]
+
 use diesel::{Queryable, Insertable};
 
-use crate::schema::accounts;
+use crate::schema::{accounts, java_installations};
 
 #[derive(Default, Queryable, Insertable)]
 pub struct Account {
@@ -10,3 +12,14 @@ pub struct Account {
     pub account_uuid: String,
     pub username: String,
 }
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+pub struct JavaInstallation {
+    pub id: Option<i32>,
+    pub path: String,
+    pub vendor: String,
+    pub version: String,
+    pub major_version: i32,
+    pub arch: String,
+    pub source: String,
+}
@@ -4,7 +4,7 @@ use liblauncher::{
         new_microsoft_login,
     },
     game_profile::Profile,
-    resources::version::get_available_versions,
+    resources::{progress::Progress, version::get_available_versions},
 };
 
 use tracing::info;
@@ -18,11 +18,11 @@ async fn run_game() {
 
     let versions = get_available_versions().await.unwrap();
 
-    let latest = versions.latest_release().get_details().await.unwrap();
+    let latest = versions.latest_release().unwrap().get_details().await.unwrap();
     latest.download_client().await.unwrap();
-    latest.download_jdk().await.unwrap();
-    latest.download_libraries(|_, _| {}).await.unwrap();
-    latest.download_assets(|_, _| {}).await.unwrap();
+    latest.download_jdk(&Progress::none()).await.unwrap();
+    latest.download_libraries(&Progress::none()).await.unwrap();
+    latest.download_assets(&Progress::none()).await.unwrap();
 
     latest.extract_natives().unwrap();
 